@@ -0,0 +1,263 @@
+use bevy::prelude::*;
+
+use crate::Gradient;
+use crate::camera::VisibleRect;
+use crate::ui::ExpressionInput;
+
+use crate::constants::{
+    FIELD_SCALE,
+    GRID_TARGET_LINES,
+    GRID_LINES_PER_DIRECTION,
+    GRID_LINE_WIDTH,
+    AXIS_LINE_WIDTH,
+    GRID_LINE_COLOR,
+    AXIS_LINE_COLOR,
+    TICK_LABEL_FONT_SIZE,
+    TICK_LABEL_COLOR,
+    TICK_LABEL_MARGIN,
+    READOUT_FONT_SIZE,
+    READOUT_OFFSET,
+};
+
+#[derive(Resource)]
+/// Whether the axes, grid and cursor readout are shown
+pub struct AxesSettings {
+    pub visible: bool, // toggled with G
+}
+
+#[derive(Component)]
+/// Struct to label a grid line
+pub struct GridLine {
+    pub vertical: bool, // whether the line is at an x value (vertical) or a y value (horizontal)
+    pub index: u32, // which line this is, counting from the left or bottom of the view
+}
+
+#[derive(Component)]
+/// Struct to label the number on a grid line
+pub struct TickLabel {
+    pub vertical: bool, // whether the label is for a vertical or horizontal grid line
+    pub index: u32, // which line the label is for
+}
+
+#[derive(Component)]
+/// Struct to label the text next to the cursor showing its position and the field there
+pub struct CursorReadout;
+
+/// Distance between grid lines so about target_lines of them cover the span. Always 1, 2 or 5 times a power of 10
+pub fn tick_spacing(span: f32, target_lines: u32) -> f32 {
+    let rough = span / target_lines.max(1) as f32;
+    let power = 10_f32.powf(rough.log10().floor());
+
+    [1., 2., 5., 10.].iter()
+        .map(|step| step * power)
+        .find(|spacing| *spacing >= rough)
+        .unwrap_or(10. * power)
+}
+
+/// Positions of the grid lines between min and max, for grid lines spaced spacing apart
+pub fn tick_positions(min: f32, max: f32, spacing: f32) -> Vec<f32> {
+    let first = (min / spacing).ceil() as i32;
+    let last = (max / spacing).floor() as i32;
+
+    (first..=last).map(|tick| tick as f32 * spacing).collect()
+}
+
+/// Text for a tick, with just enough decimal places for the spacing
+pub fn tick_text(value: f32, spacing: f32) -> String {
+    let decimals = (-spacing.log10().floor()).max(0.) as usize;
+    let value = if value.abs() < spacing / 2. { 0. } else { value }; // avoid "-0"
+
+    format!("{:.*}", decimals, value)
+}
+
+/// Spawn the grid lines, tick labels and cursor readout. They start invisible
+fn axes_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let tick_style = TextStyle {
+        font: asset_server.load("fonts/tahoma.ttf"),
+        font_size: TICK_LABEL_FONT_SIZE,
+        color: TICK_LABEL_COLOR,
+    };
+
+    for vertical in [true, false] {
+        for index in 0..GRID_LINES_PER_DIRECTION {
+            commands
+                .spawn(SpriteBundle {
+                    sprite: Sprite {
+                        color: GRID_LINE_COLOR,
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0., 0., -0.045), // above the heatmap and texture, below level curves
+                    visibility: Visibility { is_visible: false },
+                    ..default()
+                })
+                .insert(GridLine { vertical, index });
+
+            commands
+                .spawn(Text2dBundle {
+                    text: Text::from_section("", tick_style.clone())
+                        .with_alignment(if vertical { TextAlignment::TOP_CENTER } else { TextAlignment::CENTER_RIGHT }), // below the x axis, left of the y axis
+                    transform: Transform::from_xyz(0., 0., 0.9), // above the field, trails and path preview
+                    visibility: Visibility { is_visible: false },
+                    ..default()
+                })
+                .insert(TickLabel { vertical, index });
+        }
+    }
+
+    commands
+        .spawn(Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font: asset_server.load("fonts/tahoma.ttf"),
+                font_size: READOUT_FONT_SIZE,
+                color: TICK_LABEL_COLOR,
+            }).with_alignment(TextAlignment::TOP_LEFT),
+            transform: Transform::from_xyz(0., 0., 2.), // above everything, including the player
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(CursorReadout);
+}
+
+/// Toggle the axes with G, ignored while typing in an expression input
+fn axes_toggle_system(
+    keys: Res<Input<KeyCode>>,
+    expression_inputs: Query<&ExpressionInput>,
+    mut settings: ResMut<AxesSettings>,
+) {
+    if keys.just_pressed(KeyCode::G) && !expression_inputs.iter().any(|expression_input| expression_input.focused) {
+        settings.visible = !settings.visible;
+    }
+}
+
+/// Place the grid lines and tick labels whenever the camera moves or the axes are toggled. The axes through the origin
+/// are drawn thicker, and tick labels stay along the axes, or along the edge of the view when an axis is out of view
+fn grid_update(
+    settings: Res<AxesSettings>,
+    rect: Res<VisibleRect>,
+    windows: Res<Windows>,
+    mut grid_lines: Query<(&GridLine, &mut Sprite, &mut Transform, &mut Visibility), Without<TickLabel>>,
+    mut tick_labels: Query<(&TickLabel, &mut Text, &mut Transform, &mut Visibility), Without<GridLine>>,
+) {
+    if !settings.is_changed() && !rect.is_changed() {
+        return
+    }
+
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    let world_per_pixel = rect.height() / window.height(); // lines and labels stay the same size on screen when zooming
+
+    let spacing = tick_spacing(rect.height(), GRID_TARGET_LINES); // same spacing both ways so the grid is square
+    let x_ticks = tick_positions(rect.min_x, rect.max_x, spacing);
+    let y_ticks = tick_positions(rect.min_y, rect.max_y, spacing);
+
+    // labels run along the axes, kept inside the view
+    let margin = TICK_LABEL_MARGIN * world_per_pixel;
+    let label_y = 0_f32.clamp(rect.min_y + 3. * margin, rect.max_y - margin);
+    let label_x = 0_f32.clamp(rect.min_x + 5. * margin, rect.max_x - margin);
+
+    for (grid_line, mut sprite, mut transform, mut visibility) in grid_lines.iter_mut() {
+        let ticks = if grid_line.vertical { &x_ticks } else { &y_ticks };
+
+        let value = match ticks.get(grid_line.index as usize) {
+            Some(value) if settings.visible => *value,
+            _ => {
+                visibility.is_visible = false;
+                continue;
+            },
+        };
+
+        let axis = value.abs() < spacing / 2.; // line through the origin
+        let width = if axis { AXIS_LINE_WIDTH } else { GRID_LINE_WIDTH } * world_per_pixel;
+
+        sprite.color = if axis { AXIS_LINE_COLOR } else { GRID_LINE_COLOR };
+
+        if grid_line.vertical {
+            sprite.custom_size = Some(Vec2::new(width, rect.height()));
+            transform.translation.x = value;
+            transform.translation.y = rect.center().1;
+        } else {
+            sprite.custom_size = Some(Vec2::new(rect.width(), width));
+            transform.translation.x = rect.center().0;
+            transform.translation.y = value;
+        }
+
+        visibility.is_visible = true;
+    }
+
+    for (tick_label, mut text, mut transform, mut visibility) in tick_labels.iter_mut() {
+        let ticks = if tick_label.vertical { &x_ticks } else { &y_ticks };
+
+        let value = match ticks.get(tick_label.index as usize) {
+            Some(value) if settings.visible && !(tick_label.vertical && value.abs() < spacing / 2.) => *value, // origin is labeled once, on the y axis
+            _ => {
+                visibility.is_visible = false;
+                continue;
+            },
+        };
+
+        text.sections[0].value = tick_text(value, spacing);
+
+        if tick_label.vertical {
+            transform.translation.x = value;
+            transform.translation.y = label_y - margin;
+        } else {
+            transform.translation.x = label_x - margin;
+            transform.translation.y = value;
+        }
+        transform.scale = Vec3::splat(world_per_pixel);
+
+        visibility.is_visible = true;
+    }
+}
+
+/// Show the cursor's position and the field there, next to the cursor
+fn cursor_readout_system(
+    settings: Res<AxesSettings>,
+    rect: Res<VisibleRect>,
+    windows: Res<Windows>,
+    gradient: Query<&Gradient>,
+    mut readout: Query<(&mut Text, &mut Transform, &mut Visibility), With<CursorReadout>>,
+) {
+    let (mut text, mut transform, mut visibility) = readout.single_mut();
+
+    let (window, cursor) = match windows.get_primary().and_then(|window| window.cursor_position().map(|cursor| (window, cursor))) {
+        Some(window_and_cursor) if settings.visible => window_and_cursor,
+        _ => { // hidden, or cursor isn't over the window
+            visibility.is_visible = false;
+            return
+        },
+    };
+
+    let gradient = gradient.single(); // should only be 1 gradient
+    let world_per_pixel = rect.height() / window.height();
+
+    let (x, y) = rect.window_to_world(window, cursor);
+    let (field_x, field_y) = (gradient.x(x, y) / FIELD_SCALE, gradient.y(x, y) / FIELD_SCALE); // field as the player wrote it
+
+    text.sections[0].value = format!(
+        "(x, y) = ({:.2}, {:.2})\ngradient = ({:.3}, {:.3})\n|gradient| = {:.3}",
+        x, y, field_x, field_y, (field_x.powi(2) + field_y.powi(2)).sqrt(),
+    );
+
+    transform.translation.x = x + READOUT_OFFSET * world_per_pixel;
+    transform.translation.y = y - READOUT_OFFSET * world_per_pixel;
+    transform.scale = Vec3::splat(world_per_pixel);
+
+    visibility.is_visible = true;
+}
+
+/// Plugin for the axes, grid and cursor readout
+pub struct AxesPlugin;
+
+impl Plugin for AxesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AxesSettings { visible: true });
+        app.add_startup_system(axes_setup);
+        app.add_system(axes_toggle_system);
+        app.add_system(grid_update.after(axes_toggle_system));
+        app.add_system(cursor_readout_system.after(axes_toggle_system));
+    }
+}
@@ -0,0 +1,94 @@
+use std::{env, process};
+
+use the_game_of_gradients::Level;
+use the_game_of_gradients::verifier;
+use the_game_of_gradients::solver::{self, SolverOptions};
+
+const USAGE: &str = "usage: solve_levels [LEVEL] [--operations LIST] [--max-functions N] [--bounds-margin N]
+
+Tries every field that can be built from a level's buttons and lists the ones that finish it, fastest first.
+
+  LEVEL              level number (counting from 1) or id, every level if not given
+  --operations LIST  comma separated operations to join functions with, from add, subtract, multiply, divide,
+                     power, compose-x and compose-y (add,subtract,multiply,divide if not given)
+  --max-functions N  most functions in each component (4 if not given)
+  --bounds-margin N  give up on a run once it goes N world units past the level's start, end and gas cans. Faster,
+                     but misses fields that loop out that far and come back (runs aren't given up on if not given)
+
+More operations or functions make the search much slower.";
+
+/// Print a message with the usage and exit
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+/// Solve a level and list its winning fields. Returns whether any were found
+fn solve(level: &Level, options: &SolverOptions) -> bool {
+    let winning_fields = solver::solve(level, options);
+
+    println!(
+        "level {} ({}): {} winning field{}",
+        level.level_number + 1, level.id, winning_fields.len(), if winning_fields.len() == 1 { "" } else { "s" },
+    );
+    for winning_field in winning_fields.iter() {
+        let score = &winning_field.score;
+        println!(
+            "  x = {}, y = {}: {:.2} s, path length {:.1}, {} functions, {} operations\n    {}",
+            winning_field.x_text, winning_field.y_text, score.time, score.distance, score.functions, score.operations,
+            verifier::field_text(&winning_field.field),
+        );
+    }
+
+    !winning_fields.is_empty()
+}
+
+fn main() {
+    let mut options = SolverOptions::default();
+    let mut level_name = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return
+            },
+            "--operations" => match args.next().map(|list| solver::parse_operations(&list)) {
+                Some(Ok(operations)) => options.operations = operations,
+                Some(Err(error)) => usage_error(&error),
+                None => usage_error("--operations needs a list of operations"),
+            },
+            "--max-functions" => match args.next().map(|number| number.parse()) {
+                Some(Ok(max_functions)) => options.max_functions = max_functions,
+                _ => usage_error("--max-functions needs a number"),
+            },
+            "--bounds-margin" => match args.next().map(|number| number.parse()) {
+                Some(Ok(bounds_margin)) => options.bounds_margin = Some(bounds_margin),
+                _ => usage_error("--bounds-margin needs a number"),
+            },
+            _ if level_name.is_none() => level_name = Some(arg),
+            _ => usage_error(&format!("unexpected argument \"{}\"", arg)),
+        }
+    }
+
+    let levels = match verifier::load_levels(&verifier::level_file_path()) {
+        Ok(levels) => levels,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        },
+    };
+
+    let all_solved = match level_name {
+        Some(name) => match verifier::find_level(&levels, &name) {
+            Some(level) => solve(level, &options),
+            None => usage_error(&format!("no level {}", name)),
+        },
+        None => levels.iter().filter(|level| !solve(level, &options)).count() == 0, // solve every level, even after one has no solution
+    };
+
+    if !all_solved {
+        process::exit(1);
+    }
+}
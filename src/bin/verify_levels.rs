@@ -0,0 +1,76 @@
+use std::{env, process};
+
+use the_game_of_gradients::Level;
+use the_game_of_gradients::verifier;
+
+const USAGE: &str = "usage: verify_levels [LEVEL [FIELD]]
+
+Checks fields against levels without opening a window.
+
+  no arguments  check every level's known solution
+  LEVEL         level number (counting from 1) or id, to check only that level's known solution
+  FIELD         field to check instead, written like solutions in the level file, for example
+                \"Buttons(x: Some(Operation(Multiply, Function(1), Function(2))), y: Some(Function(2)))\"";
+
+/// Check a field on a level and print the report. Returns whether the level was finished
+fn check(level: &Level, field: Option<String>) -> bool {
+    let field = match field {
+        Some(text) => match verifier::parse_field(&text) {
+            Ok(field) => field,
+            Err(error) => {
+                println!("level {} ({}): invalid field: {}", level.level_number + 1, level.id, error);
+                return false
+            },
+        },
+        None => match &level.solution {
+            Some(solution) => solution.clone(),
+            None => {
+                println!("level {} ({}): no known solution", level.level_number + 1, level.id);
+                return false
+            },
+        },
+    };
+
+    match verifier::verify(level, &field) {
+        Ok(run) => {
+            println!("{}", verifier::report(level, &run));
+            run.finished
+        },
+        Err(error) => {
+            println!("level {} ({}): invalid field: {}", level.level_number + 1, level.id, error);
+            false
+        },
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if args.len() > 2 || args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        process::exit(2);
+    }
+
+    let levels = match verifier::load_levels(&verifier::level_file_path()) {
+        Ok(levels) => levels,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        },
+    };
+
+    let all_finished = match args.first() {
+        Some(name) => match verifier::find_level(&levels, name) {
+            Some(level) => check(level, args.get(1).cloned()),
+            None => {
+                eprintln!("no level {}", name);
+                process::exit(2);
+            },
+        },
+        None => levels.iter().filter(|level| !check(level, None)).count() == 0, // check every level, even after one fails
+    };
+
+    if !all_finished {
+        process::exit(1);
+    }
+}
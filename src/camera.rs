@@ -0,0 +1,261 @@
+use bevy::{
+    prelude::*,
+    input::{
+        InputSystem,
+        mouse::{MouseWheel, MouseScrollUnit},
+    },
+};
+
+use crate::{GameState, LoadingState, Level};
+use crate::ui::ExpressionInput;
+
+use crate::constants::{
+    VERTICAL_WINDOW_HEIGHT,
+    ZOOM_STEP,
+    MIN_ZOOM,
+    MAX_ZOOM,
+    PIXELS_PER_SCROLL_LINE,
+    FIT_MARGIN,
+};
+
+#[derive(Component)]
+/// Struct to label the camera the game is viewed through
+pub struct MainCamera;
+
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+/// Part of the world the camera can currently see, in world coordinates. Everything drawn across the window (arrows,
+/// streamlines, heatmap, ...) covers this rectangle, so it follows panning, zooming and resizing
+pub struct VisibleRect {
+    pub min_x: f32, // left edge
+    pub max_x: f32, // right edge
+    pub min_y: f32, // bottom edge
+    pub max_y: f32, // top edge
+}
+
+impl VisibleRect {
+    /// New method. Starts as the default view, centered on the origin, until the window is known
+    pub fn new() -> Self {
+        Self {
+            min_x: -VERTICAL_WINDOW_HEIGHT / 2.,
+            max_x: VERTICAL_WINDOW_HEIGHT / 2.,
+            min_y: -VERTICAL_WINDOW_HEIGHT / 2.,
+            max_y: VERTICAL_WINDOW_HEIGHT / 2.,
+        }
+    }
+
+    /// Width in world units
+    pub fn width(&self) -> f32 {
+        self.max_x - self.min_x
+    }
+
+    /// Height in world units
+    pub fn height(&self) -> f32 {
+        self.max_y - self.min_y
+    }
+
+    /// Point at the center
+    pub fn center(&self) -> (f32, f32) {
+        ((self.min_x + self.max_x) / 2., (self.min_y + self.max_y) / 2.)
+    }
+
+    /// Whether a point is inside
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        self.min_x <= x && x <= self.max_x && self.min_y <= y && y <= self.max_y
+    }
+
+    /// World position of a point in the window, given in pixels from the bottom left
+    pub fn window_to_world(&self, window: &Window, position: Vec2) -> (f32, f32) {
+        (
+            self.min_x + position.x / window.width() * self.width(),
+            self.min_y + position.y / window.height() * self.height(),
+        )
+    }
+}
+
+/// Part of the world visible through a camera at a position with a zoom, in a window. The camera always shows
+/// VERTICAL_WINDOW_HEIGHT world units vertically at a zoom of 1
+pub fn visible_rect(window: &Window, center: Vec2, zoom: f32) -> VisibleRect {
+    let half_height = VERTICAL_WINDOW_HEIGHT / 2. * zoom;
+    let half_width = half_height * window.width() / window.height();
+
+    VisibleRect {
+        min_x: center.x - half_width,
+        max_x: center.x + half_width,
+        min_y: center.y - half_height,
+        max_y: center.y + half_height,
+    }
+}
+
+/// Camera position and zoom that show the whole level, with some margin. Never zooms in further than the default view
+pub fn fit_level(level: &Level, window: &Window) -> (Vec2, f32) {
+    let points = level.gas_locations.iter().chain([&level.start_location, &level.end_location]);
+
+    let (mut min_x, mut max_x, mut min_y, mut max_y) = (f32::INFINITY, f32::NEG_INFINITY, f32::INFINITY, f32::NEG_INFINITY);
+    for (x, y) in points {
+        min_x = min_x.min(*x);
+        max_x = max_x.max(*x);
+        min_y = min_y.min(*y);
+        max_y = max_y.max(*y);
+    }
+
+    let center = Vec2::new((min_x + max_x) / 2., (min_y + max_y) / 2.);
+
+    let needed_height = (max_y - min_y) + 2. * FIT_MARGIN;
+    let needed_width = (max_x - min_x) + 2. * FIT_MARGIN;
+    let aspect = window.width() / window.height();
+
+    let zoom = (needed_height / VERTICAL_WINDOW_HEIGHT)
+        .max(needed_width / (VERTICAL_WINDOW_HEIGHT * aspect))
+        .clamp(1., MAX_ZOOM);
+
+    (center, zoom)
+}
+
+/// Zoom with the mouse wheel, keeping the point under the cursor still
+fn camera_zoom_system(
+    mut scroll_events: EventReader<MouseWheel>,
+    windows: Res<Windows>,
+    rect: Res<VisibleRect>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    let lines: f32 = scroll_events.iter().map(|event| match event.unit {
+        MouseScrollUnit::Line => event.y,
+        MouseScrollUnit::Pixel => event.y / PIXELS_PER_SCROLL_LINE,
+    }).sum();
+
+    if lines == 0. {
+        return
+    }
+
+    let (mut transform, mut projection) = camera.single_mut();
+
+    let old_zoom = projection.scale;
+    let new_zoom = (old_zoom * ZOOM_STEP.powf(-lines)).clamp(MIN_ZOOM, MAX_ZOOM); // scrolling up zooms in
+
+    if let Some((window, cursor)) = windows.get_primary().and_then(|window| window.cursor_position().map(|cursor| (window, cursor))) {
+        let (cursor_x, cursor_y) = rect.window_to_world(window, cursor);
+        let ratio = new_zoom / old_zoom;
+
+        transform.translation.x = cursor_x + (transform.translation.x - cursor_x) * ratio;
+        transform.translation.y = cursor_y + (transform.translation.y - cursor_y) * ratio;
+    }
+
+    projection.scale = new_zoom;
+}
+
+/// Pan by dragging with the left or middle mouse button. Drags that start on a button or input box are ignored
+fn camera_pan_system(
+    mouse_buttons: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    rect: Res<VisibleRect>,
+    interactions: Query<&Interaction>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+    mut last_cursor: Local<Option<Vec2>>, // cursor position last frame while dragging, None when not dragging
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    let pressed = mouse_buttons.pressed(MouseButton::Left) || mouse_buttons.pressed(MouseButton::Middle);
+    let just_pressed = mouse_buttons.just_pressed(MouseButton::Left) || mouse_buttons.just_pressed(MouseButton::Middle);
+
+    if !pressed {
+        *last_cursor = None;
+        return
+    }
+
+    let cursor = match window.cursor_position() {
+        Some(cursor) => cursor,
+        None => {
+            *last_cursor = None; // cursor left the window
+            return
+        },
+    };
+
+    if just_pressed {
+        let over_ui = interactions.iter().any(|interaction| *interaction != Interaction::None);
+        *last_cursor = if over_ui { None } else { Some(cursor) };
+        return
+    }
+
+    if let Some(last) = *last_cursor {
+        let world_per_pixel = rect.height() / window.height();
+        let mut transform = camera.single_mut();
+
+        transform.translation.x -= (cursor.x - last.x) * world_per_pixel;
+        transform.translation.y -= (cursor.y - last.y) * world_per_pixel;
+
+        *last_cursor = Some(cursor);
+    }
+}
+
+/// Fit the camera to the level with F, and whenever a new level starts
+fn camera_fit_system(
+    keys: Res<Input<KeyCode>>,
+    expression_inputs: Query<&ExpressionInput>,
+    windows: Res<Windows>,
+    game_state: Query<&GameState>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+    mut fitted_level: Local<Option<u32>>, // level the camera was last fitted to
+) {
+    let game_state = game_state.single();
+
+    let typing = expression_inputs.iter().any(|expression_input| expression_input.focused);
+    let fit_requested = keys.just_pressed(KeyCode::F) && !typing;
+    let new_level = *fitted_level != Some(game_state.current_level);
+
+    if !fit_requested && !new_level {
+        return
+    }
+
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    let (center, zoom) = fit_level(&game_state.level_info[game_state.current_level as usize], window);
+    let (mut transform, mut projection) = camera.single_mut();
+
+    transform.translation.x = center.x;
+    transform.translation.y = center.y;
+    projection.scale = zoom;
+
+    *fitted_level = Some(game_state.current_level);
+}
+
+/// Update the visible rectangle from the camera and window. Runs before the frame's other systems so everything
+/// drawn across the window uses where the camera is this frame
+fn visible_rect_update(
+    windows: Res<Windows>,
+    camera: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    mut rect: ResMut<VisibleRect>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    let (transform, projection) = camera.single();
+    let new_rect = visible_rect(window, transform.translation.truncate(), projection.scale);
+
+    if *rect != new_rect { // only mark as changed when it actually changes
+        *rect = new_rect;
+    }
+}
+
+/// Plugin for moving the camera around the level
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(VisibleRect::new());
+        app.add_system_to_stage(CoreStage::PreUpdate, camera_zoom_system.after(InputSystem));
+        app.add_system_to_stage(CoreStage::PreUpdate, camera_pan_system.after(InputSystem));
+        app.add_system_to_stage(CoreStage::PreUpdate, visible_rect_update.after(camera_zoom_system).after(camera_pan_system));
+        app.add_system_set(
+            SystemSet::on_update(LoadingState::Loaded) // fitting needs level info
+                .with_system(camera_fit_system)
+        );
+    }
+}
@@ -6,14 +6,107 @@ pub const GAS_CAN_SCALE: f32 = 0.05;
 
 pub const BACKGROUND_COLOR: Color = Color::rgb(0.19, 0.19, 0.19);
 
+pub const DIVISION_EPSILON: f32 = 0.01; // divisors closer to 0 than this are treated as this far from 0, so the field stays finite
 pub const FIELD_SCALE: f32 = 10.; // scalar value to increase field strength by **CHANGE THIS INSTEAD OF TICK TIME**
 pub const MOVEMENT_SCALE_PER_SECOND: f32 = 20.; // global scaling for movement per second
+pub const FIXED_TIMESTEP: f32 = 1. / 120.; // seconds of simulation in each fixed step
+pub const MAX_STEPS_PER_FRAME: u32 = 8; // most fixed steps simulated in one frame, so a slow frame can't snowball
+pub const RK45_TOLERANCE: f32 = 1e-4; // largest error in world units allowed for one adaptive integrator step
+pub const RK45_MAX_STEPS: u32 = 64; // most steps the adaptive integrator splits one fixed step's movement into
 pub const VERTICAL_WINDOW_HEIGHT: f32 = 40.; // world units for height of window
-pub const NUM_ARROWS_X: u32 = 21; // number of arrows in x direction
-pub const NUM_ARROWS_Y: u32 = 21; // number of arrows in y direction
+pub const NUM_ARROWS_X: u32 = 21; // default number of arrows in x direction
+pub const NUM_ARROWS_Y: u32 = 21; // default number of arrows in y direction
+pub const MIN_ARROWS: u32 = 5; // fewest arrows in each direction
+pub const MAX_ARROWS: u32 = 41; // most arrows in each direction
+pub const ARROW_DENSITY_STEP: u32 = 4; // change in the number of arrows in each direction per key press
+pub const ARROW_ROOT: f32 = 7.; // root taken of magnitudes for root arrow scaling
+pub const ARROW_LOG_SHARPNESS: f32 = 50.; // how quickly log arrow scaling levels off, larger values make weak arrows bigger
 pub const BASE_ARROW_SCALE: f32 = 0.001; // base scaling factor for arrows
 pub const EXPECTED_MAX_ARROW_SCALE: f32 = 10.; // expected maximum scaling factor from BASE_ARROW_SCALE for arrows (Could change to dynamic system based on max arrow size in future)
 pub const ENDING_LOCATION_ERROR: f32 = 0.5; // error allowed for ending location
+pub const VERIFY_MAX_STEPS: u64 = 120 * 120; // most fixed steps a field is simulated for when checking whether it finishes a level (2 minutes)
+pub const PAR_TOLERANCE: f32 = 0.001; // fraction a time or path length can be over par and still count as at par, for rounding
+
+// camera constants
+pub const ZOOM_STEP: f32 = 1.1; // factor the view grows or shrinks by per mouse wheel line
+pub const MIN_ZOOM: f32 = 0.25; // closest zoom, as a fraction of the default view height
+pub const MAX_ZOOM: f32 = 4.; // furthest zoom, as a multiple of the default view height
+pub const PIXELS_PER_SCROLL_LINE: f32 = 40.; // pixels of touchpad scrolling that count as one mouse wheel line
+pub const FIT_MARGIN: f32 = 3.; // world units left around the level when fitting the camera to it
+
+// path preview constants
+pub const PREVIEW_STEPS: u32 = 2400; // number of fixed steps simulated ahead for the path preview (20 seconds)
+pub const PREVIEW_DOT_COUNT: u32 = 120; // number of dots drawn along the path preview
+pub const PREVIEW_DOT_SIZE: f32 = 0.25; // width of path preview dots in world units
+pub const PREVIEW_DOT_COLOR: Color = Color::rgba(0.9, 0.9, 0.9, 0.6); // color of path preview dots
+pub const PREVIEW_MARKER_SIZE: f32 = 0.8; // width of markers where the path preview collects gas or reaches the portal
+pub const PREVIEW_GAS_MARKER_COLOR: Color = Color::rgb(0.95, 0.75, 0.2); // color of marker where the path preview collects a gas can
+pub const PREVIEW_END_MARKER_COLOR: Color = Color::rgb(0.4, 0.8, 0.4); // color of marker where the path preview reaches the portal
+pub const PREVIEW_MISSED_END_MARKER_COLOR: Color = Color::rgb(0.8, 0.35, 0.35); // color of marker where the path preview passes the portal with gas still missing
+pub const TRAIL_RUNS: u32 = 3; // number of past runs whose trails stay visible
+pub const TRAIL_MAX_POINTS: u32 = 400; // most positions recorded per run, older positions are dropped first
+pub const TRAIL_SAMPLE_DISTANCE: f32 = 0.3; // distance in world units the player moves before the trail records another position
+pub const TRAIL_WIDTH: f32 = 0.15; // width of trail lines in world units
+pub const TRAIL_COLORS: [Color; TRAIL_RUNS as usize] = [
+    Color::rgb(0.3, 0.8, 1.0), // most recent run
+    Color::rgb(1.0, 0.5, 0.3), // run before that
+    Color::rgb(0.7, 0.4, 1.0), // oldest run
+];
+pub const TRAIL_MIN_ALPHA: f32 = 0.15; // alpha of the oldest part of a trail
+
+// field render mode constants
+pub const STREAMLINE_SEEDS_X: u32 = 12; // number of streamlines seeded in the x direction
+pub const STREAMLINE_SEEDS_Y: u32 = 8; // number of streamlines seeded in the y direction
+pub const STREAMLINE_STEPS: u32 = 20; // most steps traced each way from a streamline's seed
+pub const STREAMLINE_STEP_SIZE: f32 = 0.4; // length in world units of each streamline step
+pub const STREAMLINE_WIDTH: f32 = 0.12; // width of streamlines in world units
+pub const PARTICLE_COUNT: u32 = 300; // number of particles moving through the field
+pub const PARTICLE_SIZE: f32 = 0.25; // width of particles in world units
+pub const PARTICLE_LIFETIME: f32 = 3.; // seconds before a particle respawns somewhere else
+pub const LIC_HEIGHT: u32 = 150; // height in pixels of the line integral convolution texture, the width follows the window
+pub const LIC_LENGTH: u32 = 12; // pixels followed along the field each way when smearing the texture
+
+// heatmap constants
+pub const HEATMAP_HEIGHT: u32 = 80; // height in pixels of the heatmap texture, the width follows the window
+pub const HEATMAP_LIGHTNESS: f32 = 0.35; // lightness of heatmap colors, dark so arrows stand out
+pub const HEATMAP_ALPHA: f32 = 0.5; // alpha of the heatmap over the background
+pub const LEGEND_SEGMENTS: u32 = 24; // number of blocks of color in the legend
+pub const LEGEND_WIDTH: f32 = 20.; // width of the legend's color bar in pixels
+pub const LEGEND_HEIGHT: f32 = 192.; // height of the legend's color bar in pixels
+
+// contour constants
+pub const CONTOUR_GRID_ROWS: u32 = 61; // number of rows of points where the potential is found for drawing level curves, the columns follow the window
+pub const CONTOUR_LEVELS: u32 = 15; // number of level curves drawn
+pub const CONTOUR_MAX_SEGMENTS: u32 = 6000; // most segments drawn across all level curves
+pub const CONTOUR_WIDTH: f32 = 0.08; // width of level curves in world units
+pub const CONTOUR_COLOR: Color = Color::rgba(1., 1., 1., 0.35); // color of level curves
+
+// axes constants
+pub const GRID_TARGET_LINES: u32 = 10; // rough number of horizontal grid lines in the view, the spacing is rounded to 1, 2 or 5 times a power of 10
+pub const GRID_LINES_PER_DIRECTION: u32 = 48; // most grid lines drawn in each direction
+pub const GRID_LINE_WIDTH: f32 = 1.; // width of grid lines in pixels
+pub const AXIS_LINE_WIDTH: f32 = 2.; // width of the x and y axes in pixels
+pub const GRID_LINE_COLOR: Color = Color::rgba(1., 1., 1., 0.08); // color of grid lines
+pub const AXIS_LINE_COLOR: Color = Color::rgba(1., 1., 1., 0.3); // color of the x and y axes
+pub const TICK_LABEL_FONT_SIZE: f32 = 14.; // font size of tick labels in pixels
+pub const TICK_LABEL_COLOR: Color = Color::rgba(0.9, 0.9, 0.9, 0.7); // color of tick labels and the cursor readout
+pub const TICK_LABEL_MARGIN: f32 = 4.; // pixels between tick labels and their axis
+pub const READOUT_FONT_SIZE: f32 = 16.; // font size of the cursor readout in pixels
+pub const READOUT_OFFSET: f32 = 16.; // pixels right of and below the cursor to place the cursor readout
+
+// probe constants
+pub const PROBE_FONT_SIZE: f32 = 16.; // font size of the probe tooltip in pixels
+pub const PROBE_OFFSET: f32 = 16.; // pixels left of and below the cursor to place the probe tooltip
+
+// solver constants
+pub const SOLVER_SAMPLE_POINTS: [(f32, f32); 6] = [(0.37, -1.3), (2.1, 0.7), (-3.3, 2.9), (5.5, -4.1), (-7.2, -6.6), (11., 3.3)]; // points where candidate components are compared, to skip ones that give the same field
+
+// field analysis constants
+pub const ANALYSIS_SAMPLES: u32 = 25; // number of points in each direction where curl and divergence are estimated
+pub const ANALYSIS_STEP: f32 = 0.01; // step size in world units for estimating derivatives with finite differences
+pub const ANALYSIS_MARGIN: f32 = 5.; // world units around a level's start, end and gas cans where curl and divergence are estimated
+pub const CONSERVATIVE_TOLERANCE: f32 = 0.01; // largest estimated curl, relative to the size of the field's derivatives, for a field to count as conservative
+pub const ANALYSIS_TEXT_COLOR: Color = Color::rgb(0.8, 0.8, 0.8); // color for the field analysis panel
 
 // button constants
 pub const NORMAL_BUTTON_COLOR: Color = Color::rgb(0.5, 0.5, 0.5);
@@ -26,13 +119,41 @@ pub const BUTTONS_PER_DIMENSION: u32 = 4; // number of buttons, or function comp
 pub const BUTTON_SPACING: f32 = 10.; // spacing between buttons in pixels 
 pub const BUTTON_WIDTH: f32 = 100.; // width of buttons in pixels
 pub const BUTTON_HEIGHT: f32 = 60.; // height of buttons in pixels
+pub const GROUP_BUTTON_SIZE: f32 = 36.; // width and height of buttons for grouping functions in pixels
+pub const OPERATION_BUTTON_WIDTH: f32 = 46.; // width of operation selector buttons in pixels
+pub const OPERATION_BUTTON_HEIGHT: f32 = 28.; // height of operation selector buttons in pixels
+pub const OPERATION_BUTTON_SPACING: f32 = 3.; // spacing around operation selector buttons in pixels
 
 pub const SIM_BUTTON_OFF: Color = Color::rgb(0.1, 0.8, 0.1); // color for simulate button when not simulating 
 pub const SIM_BUTTON_ON: Color = Color::rgb(0.8, 0.1, 0.1); // color for simulate button when simulating
 pub const SIM_BUTTON_OFF_HOVER: Color = Color::rgb(0.2, 0.9, 0.2); // color for simulate button when not simulating and hovered
 pub const SIM_BUTTON_ON_HOVER: Color = Color::rgb(0.9, 0.2, 0.2); // color for simulate button when simulating and hovered
 
+pub const INPUT_BOX_COLOR: Color = Color::rgb(0.25, 0.25, 0.25); // color for expression input boxes
+pub const FOCUSED_INPUT_BOX_COLOR: Color = Color::rgb(0.35, 0.35, 0.35); // color for expression input box currently being typed in
+pub const INPUT_BOX_WIDTH: f32 = 4.*BUTTON_WIDTH + 6.*BUTTON_SPACING; // width of expression input boxes in pixels (same as a row of buttons)
+pub const PARSE_ERROR_TEXT_COLOR: Color = Color::rgb(0.9, 0.4, 0.4); // color for gradient text when the typed expression can't be parsed
+
+pub const LEVEL_SELECT_BACKGROUND_COLOR: Color = Color::rgba(0.12, 0.12, 0.12, 0.95); // color behind the level select, covering the game
+pub const LEVEL_SELECT_ROW_WIDTH: f32 = 520.; // width of each level's button in the level select in pixels
+pub const LEVEL_SELECT_ROW_HEIGHT: f32 = 40.; // height of each level's button in the level select in pixels
+pub const LOCKED_LEVEL_COLOR: Color = Color::rgb(0.3, 0.3, 0.3); // color for the buttons of locked levels in the level select
+pub const LOCKED_LEVEL_TEXT_COLOR: Color = Color::rgb(0.55, 0.55, 0.55); // color for the text of locked levels in the level select
+pub const COMPLETED_LEVEL_TEXT_COLOR: Color = Color::rgb(0.6, 1.0, 0.6); // color for the text of completed levels in the level select
+
+pub const SUMMARY_BACKGROUND_COLOR: Color = Color::rgba(0.15, 0.15, 0.15, 0.9); // color behind the level complete summary
+
+pub const MAX_HINTS: u32 = 3; // hints for each level: a button in the solution, then its operations, then the whole field
+pub const HINT_BUTTON_COLOR: Color = Color::rgb(0.6, 0.5, 0.15); // color for function buttons a hint points to
+pub const HINT_TEXT_COLOR: Color = Color::rgb(0.95, 0.85, 0.45); // color for the hint text
+
+pub const FIELD_HISTORY_LENGTH: usize = 100; // most changes to the field that can be undone
+
 pub const NEW_LEVEL_TEXT_FADE_IN_SPEED: f32 = 0.5; // speed at which new level text fades in
 pub const LEVEL_COMPLETE_TEXT_COLOR: (f32, f32, f32) = (0.4, 0.8, 0.4); // rgb values for level complete text
 
-pub const MAX_GAS_CANS: u32 = 5; // maximum number of gas cans that can be collected
\ No newline at end of file
+pub const MAX_GAS_CANS: u32 = 5; // maximum number of gas cans that can be collected
+
+pub const LEVEL_FILE_PATH: &str = "game.levels.ron"; // path to level file within assets folder
+pub const SAVE_FILE_PATH: &str = "save.ron"; // path to the file player progress is saved to, from the working directory
+pub const SAVE_VERSION: u32 = 1; // version of the save format, increased whenever it changes
\ No newline at end of file
@@ -0,0 +1,228 @@
+use bevy::prelude::*;
+
+use crate::Gradient;
+use crate::field_analysis::{FieldAnalysis, field_analysis_update};
+use crate::camera::VisibleRect;
+
+use crate::constants::{
+    FIELD_SCALE,
+    CONTOUR_GRID_ROWS,
+    CONTOUR_LEVELS,
+    CONTOUR_MAX_SEGMENTS,
+    CONTOUR_WIDTH,
+    CONTOUR_COLOR,
+};
+
+/// Line segment between two points, in world coordinates
+pub type Segment = ((f32, f32), (f32, f32));
+
+#[derive(Clone, Debug, PartialEq)]
+/// Values of a potential at the points of a grid, with the first row at the bottom
+pub struct PotentialGrid {
+    pub values: Vec<f32>, // value at each point, row by row. Points where the potential isn't defined are NaN
+    pub columns: usize, // number of points in each row
+    pub rows: usize, // number of rows
+    pub origin: (f32, f32), // world position of the first point, at the bottom left
+    pub cell_size: f32, // world units between neighbouring points
+}
+
+impl PotentialGrid {
+    /// Value at a column and row of the grid
+    fn value(&self, column: usize, row: usize) -> f32 {
+        self.values[row * self.columns + column]
+    }
+
+    /// World position of a column and row of the grid
+    fn position(&self, column: usize, row: usize) -> (f32, f32) {
+        (self.origin.0 + column as f32 * self.cell_size, self.origin.1 + row as f32 * self.cell_size)
+    }
+}
+
+/// Potential of the field over the visible rectangle, for the field as the player wrote it. If the field came from a
+/// potential, that potential is evaluated directly. Otherwise, if the field is conservative, a potential is recovered
+/// by integrating along the bottom of the view, then up each column.
+/// Fields that aren't conservative have no potential
+pub fn potential_grid(gradient: &Gradient, conservative: bool, rect: &VisibleRect, grid_rows: u32) -> Option<PotentialGrid> {
+    let rows = grid_rows.max(2) as usize;
+    let cell_size = rect.height() / (rows as f32 - 1.);
+    let columns = (rect.width() / cell_size).ceil() as usize + 1; // cover the whole width
+    let origin = (rect.min_x, rect.min_y);
+
+    let mut grid = PotentialGrid {
+        values: Vec::with_capacity(columns * rows),
+        columns,
+        rows,
+        origin,
+        cell_size,
+    };
+
+    match (&gradient.potential, conservative) {
+        (Some(potential), _) => {
+            for row in 0..rows {
+                for column in 0..columns {
+                    let (x, y) = grid.position(column, row);
+                    let value = potential.evaluate(x, y);
+                    grid.values.push(if value.is_finite() { value } else { f32::NAN });
+                }
+            }
+        },
+        (None, true) => {
+            grid.values = vec![0.; columns * rows];
+
+            let field_x = |x: f32, y: f32| gradient.x(x, y) / FIELD_SCALE;
+            let field_y = |x: f32, y: f32| gradient.y(x, y) / FIELD_SCALE;
+
+            for column in 1..columns { // f(x, y0) = integral of P along the bottom, by the trapezoid rule
+                let (x, y) = grid.position(column, 0);
+                grid.values[column] = grid.values[column - 1] + cell_size * (field_x(x - cell_size, y) + field_x(x, y)) / 2.;
+            }
+
+            for row in 1..rows { // f(x, y) = f(x, y0) + integral of Q up the column
+                for column in 0..columns {
+                    let (x, y) = grid.position(column, row);
+                    grid.values[row * columns + column] = grid.values[(row - 1) * columns + column] + cell_size * (field_y(x, y - cell_size) + field_y(x, y)) / 2.;
+                }
+            }
+        },
+        (None, false) => return None,
+    }
+
+    Some(grid)
+}
+
+/// Point where the level crosses the edge between two grid points, found by linear interpolation
+fn crossing(a: (f32, f32), value_a: f32, b: (f32, f32), value_b: f32, level: f32) -> (f32, f32) {
+    let t = (level - value_a) / (value_b - value_a);
+    (a.0 + t * (b.0 - a.0), a.1 + t * (b.1 - a.1))
+}
+
+/// Segments of the curve where the potential equals the level, found with marching squares. Cells with a corner
+/// where the potential isn't defined are skipped
+pub fn marching_squares(grid: &PotentialGrid, level: f32) -> Vec<Segment> {
+    let mut segments = Vec::new();
+
+    for row in 0..grid.rows.saturating_sub(1) {
+        for column in 0..grid.columns.saturating_sub(1) {
+            // corners counterclockwise from the bottom left
+            let corners = [(column, row), (column + 1, row), (column + 1, row + 1), (column, row + 1)];
+            let values = corners.map(|(column, row)| grid.value(column, row));
+            let positions = corners.map(|(column, row)| grid.position(column, row));
+
+            if values.iter().any(|value| !value.is_finite()) {
+                continue;
+            }
+
+            let above = values.map(|value| value >= level);
+
+            // crossing on each edge, edge i going from corner i to corner i + 1
+            let mut crossings = Vec::with_capacity(4);
+            for edge in 0..4 {
+                let next = (edge + 1) % 4;
+                if above[edge] != above[next] {
+                    crossings.push(crossing(positions[edge], values[edge], positions[next], values[next], level));
+                }
+            }
+
+            match crossings.len() {
+                2 => segments.push((crossings[0], crossings[1])),
+                4 => { // saddle, decide which corners are cut off using the value at the center
+                    let center_above = values.iter().sum::<f32>() / 4. >= level;
+                    if center_above == above[0] { // corners 1 and 3 are cut off
+                        segments.push((crossings[0], crossings[1]));
+                        segments.push((crossings[2], crossings[3]));
+                    } else { // corners 0 and 2 are cut off
+                        segments.push((crossings[3], crossings[0]));
+                        segments.push((crossings[1], crossings[2]));
+                    }
+                },
+                _ => {}, // level doesn't cross this cell
+            }
+        }
+    }
+
+    segments
+}
+
+/// Segments of evenly spaced level curves of a potential, between its smallest and largest values
+pub fn contour_lines(grid: &PotentialGrid, levels: u32) -> Vec<Segment> {
+    let finite = grid.values.iter().cloned().filter(|value| value.is_finite());
+    let min = finite.clone().fold(f32::INFINITY, f32::min);
+    let max = finite.fold(f32::NEG_INFINITY, f32::max);
+
+    if max - min <= f32::EPSILON { // constant potential (or none defined) has no level curves
+        return Vec::new()
+    }
+
+    (0..levels)
+        .flat_map(|index| marching_squares(grid, min + (index as f32 + 0.5) * (max - min) / levels as f32))
+        .collect()
+}
+
+#[derive(Component)]
+/// Struct to label one segment of a level curve
+pub struct ContourSegment {
+    pub index: u32, // which segment this is, across all level curves
+}
+
+/// Spawn the segments for the level curves. They start invisible
+fn contour_setup(mut commands: Commands) {
+    for index in 0..CONTOUR_MAX_SEGMENTS {
+        commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: CONTOUR_COLOR,
+                    ..default()
+                },
+                transform: Transform::from_xyz(0., 0., -0.04), // above the heatmap and texture, below arrows
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(ContourSegment { index });
+    }
+}
+
+/// Redraw the level curves whenever the field is reanalyzed, which happens when it changes or the camera moves
+fn contour_update(
+    gradient: Query<&Gradient>,
+    analysis: Res<FieldAnalysis>,
+    rect: Res<VisibleRect>,
+    mut segments: Query<(&ContourSegment, &mut Sprite, &mut Transform, &mut Visibility)>,
+) {
+    if !analysis.is_changed() {
+        return
+    }
+
+    let gradient = gradient.single(); // should only be 1 gradient
+
+    let lines = match potential_grid(gradient, analysis.conservative, &rect, CONTOUR_GRID_ROWS) {
+        Some(grid) => contour_lines(&grid, CONTOUR_LEVELS),
+        None => Vec::new(), // no potential to draw
+    };
+
+    for (segment, mut sprite, mut transform, mut visibility) in segments.iter_mut() {
+        match lines.get(segment.index as usize) {
+            Some((start, end)) => {
+                let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+
+                sprite.custom_size = Some(Vec2::new((dx.powi(2) + dy.powi(2)).sqrt(), CONTOUR_WIDTH));
+
+                transform.translation.x = (start.0 + end.0) / 2.;
+                transform.translation.y = (start.1 + end.1) / 2.;
+                transform.rotation = Quat::from_rotation_z(dy.atan2(dx));
+
+                visibility.is_visible = true;
+            },
+            None => visibility.is_visible = false,
+        }
+    }
+}
+
+/// Plugin for drawing level curves of the potential under the field
+pub struct ContourPlugin;
+
+impl Plugin for ContourPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(contour_setup);
+        app.add_system(contour_update.after(field_analysis_update)); // needs to know whether the field is conservative
+    }
+}
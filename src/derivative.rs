@@ -0,0 +1,341 @@
+use crate::expression::{Constant, Expression, Function, Variable};
+
+impl Expression {
+    /// Partial derivative of the expression with respect to a variable, simplified for display
+    pub fn derivative(&self, variable: Variable) -> Expression {
+        self.differentiate(variable).simplify()
+    }
+
+    /// Whether the expression changes with the given variable
+    pub fn contains_variable(&self, variable: Variable) -> bool {
+        match self {
+            Expression::Variable(found) => *found == variable,
+            Expression::Number(_) | Expression::Constant(_) => false,
+            Expression::Negate(inner) | Expression::Function(_, inner) => inner.contains_variable(variable),
+            Expression::Add(lhs, rhs)
+            | Expression::Subtract(lhs, rhs)
+            | Expression::Multiply(lhs, rhs)
+            | Expression::Divide(lhs, rhs)
+            | Expression::Power(lhs, rhs) => lhs.contains_variable(variable) || rhs.contains_variable(variable),
+        }
+    }
+
+    /// Simplify the expression by folding numbers and removing identities such as "0 + a", "1a" and "a^1"
+    pub fn simplify(&self) -> Expression {
+        match self {
+            Expression::Number(_) | Expression::Variable(_) | Expression::Constant(_) => self.clone(),
+            Expression::Negate(inner) => negate(inner.simplify()),
+            Expression::Add(lhs, rhs) => add(lhs.simplify(), rhs.simplify()),
+            Expression::Subtract(lhs, rhs) => subtract(lhs.simplify(), rhs.simplify()),
+            Expression::Multiply(lhs, rhs) => multiply(lhs.simplify(), rhs.simplify()),
+            Expression::Divide(lhs, rhs) => divide(lhs.simplify(), rhs.simplify()),
+            Expression::Power(base, exponent) => power(base.simplify(), exponent.simplify()),
+            Expression::Function(function, argument) => apply(*function, argument.simplify()),
+        }
+    }
+
+    /// Unsimplified partial derivative, straight from the differentiation rules
+    fn differentiate(&self, variable: Variable) -> Expression {
+        let d = |expression: &Expression| expression.differentiate(variable);
+
+        match self {
+            Expression::Number(_) | Expression::Constant(_) => Expression::Number(0.),
+            Expression::Variable(found) if *found == variable => Expression::Number(1.),
+            Expression::Variable(_) => Expression::Number(0.), // other variable is held constant
+            Expression::Negate(inner) => Expression::Negate(Box::new(d(inner))),
+            Expression::Add(lhs, rhs) => Expression::Add(Box::new(d(lhs)), Box::new(d(rhs))),
+            Expression::Subtract(lhs, rhs) => Expression::Subtract(Box::new(d(lhs)), Box::new(d(rhs))),
+            Expression::Multiply(lhs, rhs) => Expression::Add( // product rule
+                Box::new(Expression::Multiply(Box::new(d(lhs)), rhs.clone())),
+                Box::new(Expression::Multiply(lhs.clone(), Box::new(d(rhs)))),
+            ),
+            Expression::Divide(lhs, rhs) => Expression::Divide( // quotient rule
+                Box::new(Expression::Subtract(
+                    Box::new(Expression::Multiply(Box::new(d(lhs)), rhs.clone())),
+                    Box::new(Expression::Multiply(lhs.clone(), Box::new(d(rhs)))),
+                )),
+                Box::new(Expression::Power(rhs.clone(), Box::new(Expression::Number(2.)))),
+            ),
+            Expression::Power(base, exponent) if !exponent.contains_variable(variable) => Expression::Multiply( // power rule
+                Box::new(Expression::Multiply(
+                    exponent.clone(),
+                    Box::new(Expression::Power(
+                        base.clone(),
+                        Box::new(Expression::Subtract(exponent.clone(), Box::new(Expression::Number(1.)))),
+                    )),
+                )),
+                Box::new(d(base)),
+            ),
+            Expression::Power(base, exponent) if !base.contains_variable(variable) => Expression::Multiply( // exponential rule
+                Box::new(Expression::Multiply(Box::new(self.clone()), Box::new(Expression::Function(Function::Ln, base.clone())))),
+                Box::new(d(exponent)),
+            ),
+            Expression::Power(base, exponent) => Expression::Multiply( // general rule, from a^b = exp(b ln(a))
+                Box::new(self.clone()),
+                Box::new(Expression::Add(
+                    Box::new(Expression::Multiply(Box::new(d(exponent)), Box::new(Expression::Function(Function::Ln, base.clone())))),
+                    Box::new(Expression::Divide(Box::new(Expression::Multiply(exponent.clone(), Box::new(d(base)))), base.clone())),
+                )),
+            ),
+            Expression::Function(function, argument) => Expression::Multiply( // chain rule
+                Box::new(function_derivative(*function, argument)),
+                Box::new(d(argument)),
+            ),
+        }
+    }
+}
+
+/// Derivative of a function with respect to its argument, evaluated at the argument
+fn function_derivative(function: Function, argument: &Expression) -> Expression {
+    let argument = Box::new(argument.clone());
+    let number = |value: f32| Box::new(Expression::Number(value));
+
+    match function {
+        Function::Sin => Expression::Function(Function::Cos, argument),
+        Function::Cos => Expression::Negate(Box::new(Expression::Function(Function::Sin, argument))),
+        Function::Tan => Expression::Divide(
+            number(1.),
+            Box::new(Expression::Power(Box::new(Expression::Function(Function::Cos, argument)), number(2.))),
+        ),
+        Function::Exp => Expression::Function(Function::Exp, argument),
+        Function::Ln => Expression::Divide(number(1.), argument),
+        Function::Sqrt => Expression::Divide(
+            number(1.),
+            Box::new(Expression::Multiply(number(2.), Box::new(Expression::Function(Function::Sqrt, argument)))),
+        ),
+        Function::Cbrt => Expression::Divide(
+            number(1.),
+            Box::new(Expression::Multiply(
+                number(3.),
+                Box::new(Expression::Power(Box::new(Expression::Function(Function::Cbrt, argument)), number(2.))),
+            )),
+        ),
+        Function::Abs => Expression::Divide(argument.clone(), Box::new(Expression::Function(Function::Abs, argument))), // sign of the argument
+    }
+}
+
+/// Number expression, without the "-0" that folding can produce
+fn number(value: f32) -> Expression {
+    if value == 0. {
+        Expression::Number(0.)
+    } else {
+        Expression::Number(value)
+    }
+}
+
+/// Whether an expression is exactly the given number
+fn is_number(expression: &Expression, value: f32) -> bool {
+    *expression == Expression::Number(value)
+}
+
+/// Whether a folded value is clean enough to show instead of the expression it came from
+fn is_whole(value: f32) -> bool {
+    value.is_finite() && value.fract() == 0.
+}
+
+/// Split a product into its number coefficient and the rest, as in "3x^2*y" into (3, x^2*y)
+fn split_coefficient(expression: Expression) -> (f32, Expression) {
+    match expression {
+        Expression::Number(value) => (value, Expression::Number(1.)),
+        Expression::Multiply(lhs, rhs) => match split_coefficient(*lhs) { // products are grouped from the left, so the coefficient is leftmost
+            (coefficient, rest) if is_number(&rest, 1.) => (coefficient, *rhs),
+            (coefficient, rest) => (coefficient, Expression::Multiply(Box::new(rest), rhs)),
+        },
+        other => (1., other),
+    }
+}
+
+/// Put a product split by split_coefficient back together, without simplifying it
+fn with_coefficient(coefficient: f32, rest: Expression) -> Expression {
+    match rest {
+        rest if coefficient == 1. => rest,
+        rest if is_number(&rest, 1.) => Expression::Number(coefficient),
+        Expression::Multiply(lhs, rhs) => Expression::Multiply(Box::new(with_coefficient(coefficient, *lhs)), rhs), // keep coefficient leftmost
+        rest => Expression::Multiply(Box::new(Expression::Number(coefficient)), Box::new(rest)),
+    }
+}
+
+/// Simplified negation. Signs are kept outside of products, so "-(6x)" shows as "-6x" 
+fn negate(inner: Expression) -> Expression {
+    match inner {
+        Expression::Number(value) => number(-value),
+        Expression::Negate(inner) => *inner, // --a = a
+        Expression::Subtract(lhs, rhs) => subtract(*rhs, *lhs), // -(a - b) = b - a
+        inner => match split_coefficient(inner) {
+            (coefficient, rest) if coefficient < 0. => multiply(number(-coefficient), rest), // signs cancel
+            (coefficient, rest) => Expression::Negate(Box::new(with_coefficient(coefficient, rest))),
+        },
+    }
+}
+
+/// Simplified sum
+fn add(lhs: Expression, rhs: Expression) -> Expression {
+    match (lhs, rhs) {
+        (Expression::Number(lhs), Expression::Number(rhs)) => number(lhs + rhs),
+        (lhs, rhs) if is_number(&lhs, 0.) => rhs,
+        (lhs, rhs) if is_number(&rhs, 0.) => lhs,
+        (lhs, Expression::Negate(rhs)) => subtract(lhs, *rhs), // a + -b = a - b
+        (lhs, Expression::Number(rhs)) if rhs < 0. => subtract(lhs, number(-rhs)),
+        (Expression::Negate(lhs), rhs) => subtract(rhs, *lhs), // -a + b = b - a
+        (lhs, rhs) if lhs == rhs => multiply(Expression::Number(2.), lhs), // a + a = 2a
+        (lhs, rhs) => Expression::Add(Box::new(lhs), Box::new(rhs)),
+    }
+}
+
+/// Simplified difference
+fn subtract(lhs: Expression, rhs: Expression) -> Expression {
+    match (lhs, rhs) {
+        (Expression::Number(lhs), Expression::Number(rhs)) => number(lhs - rhs),
+        (lhs, rhs) if is_number(&rhs, 0.) => lhs,
+        (lhs, rhs) if is_number(&lhs, 0.) => negate(rhs),
+        (lhs, Expression::Negate(rhs)) => add(lhs, *rhs), // a - -b = a + b
+        (lhs, Expression::Number(rhs)) if rhs < 0. => add(lhs, number(-rhs)),
+        (lhs, rhs) if lhs == rhs => Expression::Number(0.), // a - a = 0
+        (lhs, rhs) => Expression::Subtract(Box::new(lhs), Box::new(rhs)),
+    }
+}
+
+/// Simplified product. Numbers are moved to the front, so "x*2" becomes "2x", and products are grouped from the left
+fn multiply(lhs: Expression, rhs: Expression) -> Expression {
+    match (lhs, rhs) {
+        (Expression::Number(lhs), Expression::Number(rhs)) => number(lhs * rhs),
+        (lhs, rhs) if is_number(&lhs, 0.) || is_number(&rhs, 0.) => Expression::Number(0.),
+        (lhs, rhs) if is_number(&lhs, 1.) => rhs,
+        (lhs, rhs) if is_number(&rhs, 1.) => lhs,
+        (lhs, rhs) if is_number(&lhs, -1.) => negate(rhs),
+        (lhs, rhs) if is_number(&rhs, -1.) => negate(lhs),
+        (other, Expression::Number(coefficient)) => multiply(Expression::Number(coefficient), other), // coefficient first
+        (Expression::Number(coefficient), other) if coefficient < 0. => negate(multiply(number(-coefficient), other)),
+        (Expression::Negate(lhs), rhs) => negate(multiply(*lhs, rhs)),
+        (lhs, Expression::Negate(rhs)) => negate(multiply(lhs, *rhs)),
+        (lhs, Expression::Divide(numerator, denominator)) => divide(multiply(lhs, *numerator), *denominator), // a(b/c) = ab/c
+        (Expression::Divide(numerator, denominator), rhs) => divide(multiply(*numerator, rhs), *denominator), // (a/b)c = ac/b
+        (Expression::Number(outer), Expression::Multiply(lhs, rhs)) => match split_coefficient(Expression::Multiply(lhs, rhs)) {
+            (coefficient, rest) if coefficient != 1. => multiply(number(outer * coefficient), rest), // 2(3x) = 6x
+            (_, Expression::Multiply(lhs, rhs)) => Expression::Multiply(Box::new(multiply(Expression::Number(outer), *lhs)), rhs), // 3(x^2y^2) = 3x^2*y^2
+            (_, rest) => Expression::Multiply(Box::new(Expression::Number(outer)), Box::new(rest)),
+        },
+        (lhs, Expression::Multiply(inner, rhs)) if matches!(*inner, Expression::Number(_)) => {
+            let (coefficient, rest) = split_coefficient(Expression::Multiply(inner, rhs));
+            multiply(Expression::Number(coefficient), multiply(lhs, rest)) // pull coefficients out front, as in x(2y) = 2xy
+        },
+        (lhs, rhs) if lhs == rhs => power(lhs, Expression::Number(2.)), // aa = a^2
+        (lhs, rhs) => Expression::Multiply(Box::new(lhs), Box::new(rhs)),
+    }
+}
+
+/// Simplified quotient. Coefficients that divide evenly are cancelled, so "6x/(2y)" becomes "3x/y"
+fn divide(lhs: Expression, rhs: Expression) -> Expression {
+    match (lhs, rhs) {
+        (Expression::Number(lhs), Expression::Number(rhs)) if rhs != 0. && is_whole(lhs / rhs) => number(lhs / rhs),
+        (lhs, _) if is_number(&lhs, 0.) => Expression::Number(0.),
+        (lhs, rhs) if is_number(&rhs, 1.) => lhs,
+        (lhs, rhs) if is_number(&rhs, -1.) => negate(lhs),
+        (Expression::Negate(lhs), rhs) => negate(divide(*lhs, rhs)),
+        (lhs, Expression::Negate(rhs)) => negate(divide(lhs, *rhs)),
+        (Expression::Number(lhs), rhs) if lhs < 0. => negate(divide(number(-lhs), rhs)),
+        (lhs, rhs) if lhs == rhs => Expression::Number(1.), // a/a = 1
+        (lhs, Expression::Power(base, exponent)) if lhs == *base && matches!(*exponent, Expression::Number(_)) => {
+            divide(Expression::Number(1.), power(*base, subtract(*exponent, Expression::Number(1.)))) // a/a^n = 1/a^(n-1)
+        },
+        (lhs, rhs) => {
+            let (numerator_coefficient, numerator) = split_coefficient(lhs);
+            let (denominator_coefficient, denominator) = split_coefficient(rhs);
+            let ratio = numerator_coefficient / denominator_coefficient;
+
+            if denominator_coefficient != 1. && is_whole(ratio) {
+                multiply(number(ratio), divide(numerator, denominator))
+            } else {
+                Expression::Divide(
+                    Box::new(with_coefficient(numerator_coefficient, numerator)),
+                    Box::new(with_coefficient(denominator_coefficient, denominator)),
+                )
+            }
+        },
+    }
+}
+
+/// Simplified power
+fn power(base: Expression, exponent: Expression) -> Expression {
+    match (base, exponent) {
+        (Expression::Number(base), Expression::Number(exponent)) if is_whole(base.powf(exponent)) => number(base.powf(exponent)),
+        (_, exponent) if is_number(&exponent, 0.) => Expression::Number(1.),
+        (base, exponent) if is_number(&exponent, 1.) => base,
+        (base, _) if is_number(&base, 1.) => Expression::Number(1.),
+        (base, exponent) => Expression::Power(Box::new(base), Box::new(exponent)),
+    }
+}
+
+/// Simplified function application. Functions of numbers are only folded when the result is whole, as in "cos(0)"
+fn apply(function: Function, argument: Expression) -> Expression {
+    match (function, argument) {
+        (function, Expression::Number(value)) if is_whole(function.apply(value)) => number(function.apply(value)),
+        (Function::Ln, Expression::Function(Function::Exp, inner)) => *inner, // ln(exp(a)) = a
+        (Function::Ln, Expression::Constant(Constant::E)) => Expression::Number(1.), // ln(e) = 1
+        (function, argument) => Expression::Function(function, Box::new(argument)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINTS: [(f32, f32); 4] = [(0.6, 1.4), (1.3, 0.7), (2.1, 0.4), (0.9, 2.3)]; // points with x, y > 0, so ln and sqrt are defined
+    const STEP: f32 = 1e-3; // step for central differences
+
+    /// Check a symbolic partial derivative against central differences at every point
+    fn assert_derivative_matches(input: &str, variable: Variable) {
+        let expression = Expression::parse(input).unwrap();
+        let derivative = expression.derivative(variable);
+
+        for (x, y) in POINTS {
+            let numeric = match variable {
+                Variable::X => (expression.evaluate(x + STEP, y) - expression.evaluate(x - STEP, y)) / (2.*STEP),
+                Variable::Y => (expression.evaluate(x, y + STEP) - expression.evaluate(x, y - STEP)) / (2.*STEP),
+            };
+            let symbolic = derivative.evaluate(x, y);
+
+            assert!(
+                (symbolic - numeric).abs() <= 2e-2 * (1. + numeric.abs()),
+                "d/d{:?} of \"{}\" is \"{}\", which gives {} at ({}, {}) but central differences give {}",
+                variable, input, derivative, symbolic, x, y, numeric,
+            );
+        }
+    }
+
+    #[test]
+    fn derivatives_match_central_differences() {
+        let inputs = [
+            "x^2 + y^2", "3x^2y", "xy/(x + y)", "-x^3", "x^y", "2^x", "e^(2x)", "sin(x)cos(y)", "tan(x/4)", "exp(-xy)",
+            "ln(x^2 + y)", "sqrt(xy)", "cbrt(x + y)", "abs(x - y)", "x/y - y/x", "(x + 1)^-2", "pi*x", "sin(cos(x))y",
+        ];
+
+        for input in inputs {
+            assert_derivative_matches(input, Variable::X);
+            assert_derivative_matches(input, Variable::Y);
+        }
+    }
+
+    #[test]
+    fn simplify_keeps_values_and_removes_identities() {
+        let cases = [
+            ("0 + x", "x"), ("x*1", "x"), ("x^1", "x"), ("x^0", "1"), ("x - x", "0"), ("x*2", "2x"), ("2(3x)", "6x"),
+            ("--x", "x"), ("x + x", "2x"), ("6x/(2y)", "3x/y"), ("ln(exp(x))", "x"), ("ln(e)", "1"), ("cos(0)", "1"),
+        ];
+
+        for (input, expected) in cases {
+            let expression = Expression::parse(input).unwrap();
+            let simplified = expression.simplify();
+            assert_eq!(simplified.to_string(), expected, "\"{}\" simplified to \"{}\"", input, simplified);
+
+            for (x, y) in POINTS {
+                assert!((simplified.evaluate(x, y) - expression.evaluate(x, y)).abs() < 1e-4, "\"{}\" changed value when simplified", input);
+            }
+        }
+    }
+
+    #[test]
+    fn derivative_of_natural_exponential_has_no_ln_e() {
+        assert_eq!(Expression::parse("e^(2x)").unwrap().derivative(Variable::X).to_string(), "2e^(2x)");
+    }
+}
@@ -0,0 +1,590 @@
+use std::fmt;
+use std::f32::consts::{PI, E};
+
+use crate::constants::DIVISION_EPSILON;
+
+/// Variables that can appear in an expression
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Variable {
+    X,
+    Y,
+}
+
+/// Named constants that can appear in an expression
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Constant {
+    Pi,
+    E,
+}
+
+/// Functions of a single argument that can appear in an expression
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Exp,
+    Ln,
+    Sqrt,
+    Cbrt,
+    Abs,
+}
+
+impl Function {
+    /// Name of the function as written in expressions
+    pub fn name(&self) -> &'static str {
+        match self {
+            Function::Sin => "sin",
+            Function::Cos => "cos",
+            Function::Tan => "tan",
+            Function::Exp => "exp",
+            Function::Ln => "ln",
+            Function::Sqrt => "sqrt",
+            Function::Cbrt => "cbrt",
+            Function::Abs => "abs",
+        }
+    }
+
+    /// Apply the function to a value
+    pub fn apply(&self, value: f32) -> f32 {
+        match self {
+            Function::Sin => value.sin(),
+            Function::Cos => value.cos(),
+            Function::Tan => value.tan(),
+            Function::Exp => value.exp(),
+            Function::Ln => value.ln(),
+            Function::Sqrt => value.sqrt(),
+            Function::Cbrt => value.cbrt(),
+            Function::Abs => value.abs(),
+        }
+    }
+}
+
+/// Compiled expression tree of a function of x and y
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Number(f32),
+    Variable(Variable),
+    Constant(Constant),
+    Negate(Box<Expression>),
+    Add(Box<Expression>, Box<Expression>),
+    Subtract(Box<Expression>, Box<Expression>),
+    Multiply(Box<Expression>, Box<Expression>),
+    Divide(Box<Expression>, Box<Expression>),
+    Power(Box<Expression>, Box<Expression>),
+    Function(Function, Box<Expression>),
+}
+
+/// Error from parsing an expression
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub message: String, // what went wrong
+    pub position: usize, // character position in the input where it went wrong
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (at character {})", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Expression {
+    /// Parse a string such as "x^2", "cbrt(y)", "xy" or "cosx" into an expression
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, position: 0, input_length: input.chars().count() };
+
+        let expression = parser.parse_sum()?;
+
+        match parser.peek() {
+            None => Ok(expression),
+            Some((token, position)) => Err(ParseError {
+                message: format!("unexpected {}", token.describe()),
+                position,
+            }),
+        }
+    }
+
+    /// Evaluate the expression at the point (x, y)
+    pub fn evaluate(&self, x: f32, y: f32) -> f32 {
+        match self {
+            Expression::Number(value) => *value,
+            Expression::Variable(Variable::X) => x,
+            Expression::Variable(Variable::Y) => y,
+            Expression::Constant(Constant::Pi) => PI,
+            Expression::Constant(Constant::E) => E,
+            Expression::Negate(inner) => -inner.evaluate(x, y),
+            Expression::Add(lhs, rhs) => lhs.evaluate(x, y) + rhs.evaluate(x, y),
+            Expression::Subtract(lhs, rhs) => lhs.evaluate(x, y) - rhs.evaluate(x, y),
+            Expression::Multiply(lhs, rhs) => lhs.evaluate(x, y) * rhs.evaluate(x, y),
+            Expression::Divide(lhs, rhs) => safe_divide(lhs.evaluate(x, y), rhs.evaluate(x, y)),
+            Expression::Power(base, exponent) => base.evaluate(x, y).powf(exponent.evaluate(x, y)),
+            Expression::Function(function, argument) => function.apply(argument.evaluate(x, y)),
+        }
+    }
+
+    /// Replace every occurrence of a variable with another expression
+    pub fn substitute(&self, variable: Variable, replacement: &Expression) -> Expression {
+        let substitute = |expression: &Expression| Box::new(expression.substitute(variable, replacement));
+
+        match self {
+            Expression::Variable(found) if *found == variable => replacement.clone(),
+            Expression::Number(_) | Expression::Variable(_) | Expression::Constant(_) => self.clone(),
+            Expression::Negate(inner) => Expression::Negate(substitute(inner)),
+            Expression::Add(lhs, rhs) => Expression::Add(substitute(lhs), substitute(rhs)),
+            Expression::Subtract(lhs, rhs) => Expression::Subtract(substitute(lhs), substitute(rhs)),
+            Expression::Multiply(lhs, rhs) => Expression::Multiply(substitute(lhs), substitute(rhs)),
+            Expression::Divide(lhs, rhs) => Expression::Divide(substitute(lhs), substitute(rhs)),
+            Expression::Power(base, exponent) => Expression::Power(substitute(base), substitute(exponent)),
+            Expression::Function(function, argument) => Expression::Function(*function, substitute(argument)),
+        }
+    }
+
+    /// Binding strength of the outermost operation, used to decide where parentheses are needed when displaying
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Expression::Add(..) | Expression::Subtract(..) => 1,
+            Expression::Multiply(..) | Expression::Divide(..) => 2,
+            Expression::Negate(..) => 3,
+            Expression::Power(..) => 4,
+            Expression::Number(value) if *value < 0. => 3, // negative numbers display like a negation
+            Expression::Number(_) | Expression::Variable(_) | Expression::Constant(_) | Expression::Function(..) => 5,
+        }
+    }
+
+    /// Whether the expression can be written directly after a number or variable to mean multiplication (as in "2x" or "xy")
+    fn can_follow_implicitly(&self) -> bool {
+        match self {
+            Expression::Variable(_) | Expression::Constant(_) | Expression::Function(..) => true,
+            Expression::Power(base, _) => matches!(**base, Expression::Variable(_) | Expression::Constant(_)),
+            _ => false,
+        }
+    }
+}
+
+/// Divide two values. Divisors closer to 0 than DIVISION_EPSILON are pushed out to DIVISION_EPSILON (keeping their sign),
+/// so dividing by something near 0 gives a large but finite value rather than infinity or NaN
+pub fn safe_divide(numerator: f32, divisor: f32) -> f32 {
+    if divisor.abs() < DIVISION_EPSILON {
+        numerator / DIVISION_EPSILON.copysign(divisor)
+    } else {
+        numerator / divisor
+    }
+}
+
+/// Write an expression, wrapped in parentheses if it binds less tightly than required
+fn write_operand(f: &mut fmt::Formatter, expression: &Expression, minimum_precedence: u8) -> fmt::Result {
+    if expression.precedence() < minimum_precedence {
+        write!(f, "({})", expression)
+    } else {
+        write!(f, "{}", expression)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expression::Number(value) => write!(f, "{}", value),
+            Expression::Variable(Variable::X) => write!(f, "x"),
+            Expression::Variable(Variable::Y) => write!(f, "y"),
+            Expression::Constant(Constant::Pi) => write!(f, "pi"),
+            Expression::Constant(Constant::E) => write!(f, "e"),
+            Expression::Negate(inner) => {
+                write!(f, "-")?;
+                write_operand(f, inner, 2) // "-(ab)" and "(-a)b" are equal, so products don't need parentheses
+            },
+            Expression::Add(lhs, rhs) => {
+                write_operand(f, lhs, 1)?;
+                write!(f, " + ")?;
+                write_operand(f, rhs, 2) // right side of a sum only needs parentheses for another sum
+            },
+            Expression::Subtract(lhs, rhs) => {
+                write_operand(f, lhs, 1)?;
+                write!(f, " - ")?;
+                write_operand(f, rhs, 2)
+            },
+            Expression::Multiply(lhs, rhs) => {
+                let implicit = match **lhs {
+                    Expression::Number(value) => value >= 0. && rhs.can_follow_implicitly(), // "2x"
+                    Expression::Variable(_) => matches!(**rhs, Expression::Variable(_)), // "xy"
+                    _ => false,
+                };
+
+                write_operand(f, lhs, 2)?;
+                if !implicit {
+                    write!(f, "*")?;
+                }
+                write_operand(f, rhs, 4) // negative right sides get parentheses, as in "x*(-1)"
+            },
+            Expression::Divide(lhs, rhs) => {
+                write_operand(f, lhs, 2)?;
+                write!(f, "/")?;
+                write_operand(f, rhs, 4)
+            },
+            Expression::Power(base, exponent) => {
+                write_operand(f, base, 5)?;
+                write!(f, "^")?;
+                write_operand(f, exponent, 3) // power is right associative, so the exponent may itself be a power
+            },
+            Expression::Function(function, argument) => write!(f, "{}({})", function.name(), argument),
+        }
+    }
+}
+
+/// Tokens produced from an expression string
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f32),
+    Variable(Variable),
+    Constant(Constant),
+    Function(Function),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    LeftParen,
+    RightParen,
+}
+
+impl Token {
+    /// Description of the token for error messages
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(value) => format!("number {}", value),
+            Token::Variable(Variable::X) => String::from("x"),
+            Token::Variable(Variable::Y) => String::from("y"),
+            Token::Constant(Constant::Pi) => String::from("pi"),
+            Token::Constant(Constant::E) => String::from("e"),
+            Token::Function(function) => format!("function {}", function.name()),
+            Token::Plus => String::from("'+'"),
+            Token::Minus => String::from("'-'"),
+            Token::Star => String::from("'*'"),
+            Token::Slash => String::from("'/'"),
+            Token::Caret => String::from("'^'"),
+            Token::LeftParen => String::from("'('"),
+            Token::RightParen => String::from("')'"),
+        }
+    }
+
+    /// Whether this token can start an operand, so that it can be multiplied implicitly (as in "2x" or "x(y+1)")
+    fn starts_operand(&self) -> bool {
+        matches!(self, Token::Number(_) | Token::Variable(_) | Token::Constant(_) | Token::Function(_) | Token::LeftParen)
+    }
+}
+
+/// Names recognized inside a run of letters. Longer names come first so that "exp" is not read as "e" followed by "xp"
+const NAMES: [(&str, Token); 12] = [
+    ("sqrt", Token::Function(Function::Sqrt)),
+    ("cbrt", Token::Function(Function::Cbrt)),
+    ("sin", Token::Function(Function::Sin)),
+    ("cos", Token::Function(Function::Cos)),
+    ("tan", Token::Function(Function::Tan)),
+    ("exp", Token::Function(Function::Exp)),
+    ("abs", Token::Function(Function::Abs)),
+    ("ln", Token::Function(Function::Ln)),
+    ("pi", Token::Constant(Constant::Pi)),
+    ("e", Token::Constant(Constant::E)),
+    ("x", Token::Variable(Variable::X)),
+    ("y", Token::Variable(Variable::Y)),
+];
+
+/// Split an expression string into tokens, each with the character position it started at
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let characters: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+
+    while position < characters.len() {
+        let character = characters[position];
+
+        if character.is_whitespace() {
+            position += 1;
+        } else if character.is_ascii_digit() || character == '.' {
+            // read the whole number
+            let start = position;
+            while position < characters.len() && (characters[position].is_ascii_digit() || characters[position] == '.') {
+                position += 1;
+            }
+
+            let number_string: String = characters[start..position].iter().collect();
+            match number_string.parse::<f32>() {
+                Ok(value) => tokens.push((Token::Number(value), start)),
+                Err(_) => return Err(ParseError { message: format!("invalid number \"{}\"", number_string), position: start }),
+            }
+
+            // "1e5" would otherwise read as 1*e*5, which is never what was meant
+            let exponent = matches!(characters.get(position), Some('e' | 'E'))
+                && characters.get(position + 1).is_some_and(|c| c.is_ascii_digit());
+            if exponent {
+                return Err(ParseError { message: String::from("scientific notation isn't supported, write numbers like 1e5 as 1*10^5"), position });
+            }
+        } else if character.is_alphabetic() {
+            // a run of letters can hold several names written together, such as "xy" or "cosx"
+            let rest: String = characters[position..].iter().collect::<String>().to_lowercase();
+
+            match NAMES.iter().find(|(name, _)| rest.starts_with(name)) {
+                Some((name, token)) => {
+                    tokens.push((token.clone(), position));
+                    position += name.len();
+                },
+                None => {
+                    let unknown: String = characters[position..].iter().take_while(|c| c.is_alphabetic()).collect();
+                    return Err(ParseError { message: format!("unknown name \"{}\"", unknown), position })
+                },
+            }
+        } else {
+            let token = match character {
+                '+' => Token::Plus,
+                '-' => Token::Minus,
+                '*' => Token::Star,
+                '/' => Token::Slash,
+                '^' => Token::Caret,
+                '(' => Token::LeftParen,
+                ')' => Token::RightParen,
+                _ => return Err(ParseError { message: format!("unexpected character '{}'", character), position }),
+            };
+            tokens.push((token, position));
+            position += 1;
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser over a list of tokens
+struct Parser {
+    tokens: Vec<(Token, usize)>, // tokens with their positions
+    position: usize, // index of the next token
+    input_length: usize, // length of the input, for errors at the end of the input
+}
+
+impl Parser {
+    /// Look at the next token without consuming it
+    fn peek(&self) -> Option<(Token, usize)> {
+        self.tokens.get(self.position).cloned()
+    }
+
+    /// Consume the next token
+    fn next(&mut self) -> Option<(Token, usize)> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    /// sum := product (('+' | '-') product)*
+    fn parse_sum(&mut self) -> Result<Expression, ParseError> {
+        let mut expression = self.parse_product()?;
+
+        loop {
+            match self.peek() {
+                Some((Token::Plus, _)) => {
+                    self.next();
+                    expression = Expression::Add(Box::new(expression), Box::new(self.parse_product()?));
+                },
+                Some((Token::Minus, _)) => {
+                    self.next();
+                    expression = Expression::Subtract(Box::new(expression), Box::new(self.parse_product()?));
+                },
+                _ => return Ok(expression),
+            }
+        }
+    }
+
+    /// product := unary (('*' | '/') unary | unary)*   (a unary directly after another means multiplication)
+    fn parse_product(&mut self) -> Result<Expression, ParseError> {
+        let mut expression = self.parse_unary()?;
+
+        loop {
+            match self.peek() {
+                Some((Token::Star, _)) => {
+                    self.next();
+                    expression = Expression::Multiply(Box::new(expression), Box::new(self.parse_unary()?));
+                },
+                Some((Token::Slash, _)) => {
+                    self.next();
+                    expression = Expression::Divide(Box::new(expression), Box::new(self.parse_unary()?));
+                },
+                Some((token, _)) if token.starts_operand() => {
+                    expression = Expression::Multiply(Box::new(expression), Box::new(self.parse_unary()?)); // implicit multiplication
+                },
+                _ => return Ok(expression),
+            }
+        }
+    }
+
+    /// unary := '-' unary | power
+    fn parse_unary(&mut self) -> Result<Expression, ParseError> {
+        match self.peek() {
+            Some((Token::Minus, _)) => {
+                self.next();
+                Ok(Expression::Negate(Box::new(self.parse_unary()?)))
+            },
+            _ => self.parse_power(),
+        }
+    }
+
+    /// power := primary ('^' unary)?   (right associative, and the exponent may be negative)
+    fn parse_power(&mut self) -> Result<Expression, ParseError> {
+        let base = self.parse_primary()?;
+
+        match self.peek() {
+            Some((Token::Caret, _)) => {
+                self.next();
+                Ok(Expression::Power(Box::new(base), Box::new(self.parse_unary()?)))
+            },
+            _ => Ok(base),
+        }
+    }
+
+    /// primary := number | variable | constant | function argument | '(' sum ')'
+    fn parse_primary(&mut self) -> Result<Expression, ParseError> {
+        match self.next() {
+            Some((Token::Number(value), _)) => Ok(Expression::Number(value)),
+            Some((Token::Variable(variable), _)) => Ok(Expression::Variable(variable)),
+            Some((Token::Constant(constant), _)) => Ok(Expression::Constant(constant)),
+            Some((Token::Function(function), _)) => {
+                // "cos(x)" takes the parenthesized argument, "cosx^2" takes the power that follows
+                let argument = match self.peek() {
+                    Some((Token::LeftParen, _)) => self.parse_primary()?,
+                    _ => self.parse_power()?,
+                };
+                Ok(Expression::Function(function, Box::new(argument)))
+            },
+            Some((Token::LeftParen, _)) => {
+                let expression = self.parse_sum()?;
+                match self.next() {
+                    Some((Token::RightParen, _)) => Ok(expression),
+                    Some((token, position)) => Err(ParseError { message: format!("expected ')' but found {}", token.describe()), position }),
+                    None => Err(ParseError { message: String::from("missing ')'"), position: self.input_length }),
+                }
+            },
+            Some((token, position)) => Err(ParseError { message: format!("unexpected {}", token.describe()), position }),
+            None => Err(ParseError { message: String::from("unexpected end of expression"), position: self.input_length }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINTS: [(f32, f32); 4] = [(0.5, 1.5), (1.3, -0.7), (-2.1, 0.4), (2.7, 3.1)]; // points expressions are compared at
+
+    /// Parse an expression that is expected to be valid
+    fn parse(input: &str) -> Expression {
+        Expression::parse(input).unwrap_or_else(|error| panic!("\"{}\" didn't parse: {}", input, error))
+    }
+
+    /// Whether two values are equal up to rounding, treating NaN as equal to NaN
+    fn close(a: f32, b: f32) -> bool {
+        (a.is_nan() && b.is_nan()) || (a - b).abs() <= 1e-4 * (1. + a.abs().max(b.abs()))
+    }
+
+    /// Check that an input gives the same values as a function of x and y at every point
+    fn assert_evaluates(input: &str, expected: impl Fn(f32, f32) -> f32) {
+        let expression = parse(input);
+        for (x, y) in POINTS {
+            let value = expression.evaluate(x, y);
+            assert!(close(value, expected(x, y)), "\"{}\" at ({}, {}) gave {}, expected {}", input, x, y, value, expected(x, y));
+        }
+    }
+
+    #[test]
+    fn operators_follow_precedence() {
+        assert_evaluates("1 + 2*3", |_, _| 7.);
+        assert_evaluates("8 - 2 - 1", |_, _| 5.); // left associative
+        assert_evaluates("8/4/2", |_, _| 1.);
+        assert_evaluates("x + y*2", |x, y| x + 2.*y);
+        assert_evaluates("(x + y)*2", |x, y| (x + y)*2.);
+        assert_evaluates("2*x^2", |x, _| 2.*x.powi(2));
+    }
+
+    #[test]
+    fn power_is_right_associative() {
+        assert_evaluates("2^3^2", |_, _| 512.);
+        assert_evaluates("x^2^0.5", |x, _| x.powf(2f32.powf(0.5)));
+        assert_evaluates("2^-1", |_, _| 0.5); // exponent may be negative
+    }
+
+    #[test]
+    fn unary_minus_binds_below_power() {
+        assert_evaluates("-x^2", |x, _| -x.powi(2));
+        assert_evaluates("(-x)^2", |x, _| x.powi(2));
+        assert_evaluates("--x", |x, _| x);
+        assert_evaluates("x*-y", |x, y| -x*y);
+        assert_evaluates("2 - -3", |_, _| 5.);
+    }
+
+    #[test]
+    fn implicit_multiplication() {
+        assert_evaluates("xy", |x, y| x*y);
+        assert_evaluates("2x y", |x, y| 2.*x*y);
+        assert_evaluates("cosx", |x, _| x.cos());
+        assert_evaluates("cosx^2", |x, _| x.powi(2).cos()); // function takes the power that follows
+        assert_evaluates("2(x + 1)", |x, _| 2.*(x + 1.));
+        assert_evaluates("x(y + 1)", |x, y| x*(y + 1.));
+        assert_evaluates("sin(x)cos(y)", |x, y| x.sin()*y.cos());
+    }
+
+    #[test]
+    fn constants_and_functions() {
+        assert_evaluates("pi", |_, _| PI);
+        assert_evaluates("e", |_, _| E);
+        assert_evaluates("2pi", |_, _| 2.*PI);
+        assert_evaluates("sin(x) + cos(y) + tan(x)", |x, y| x.sin() + y.cos() + x.tan());
+        assert_evaluates("exp(x) + ln(abs(y))", |x, y| x.exp() + y.abs().ln());
+        assert_evaluates("sqrt(abs(x)) + cbrt(y)", |x, y| x.abs().sqrt() + y.cbrt());
+        assert_evaluates("SIN(X)", |x, _| x.sin()); // names are case insensitive
+    }
+
+    #[test]
+    fn division_by_zero_is_finite() {
+        assert!(parse("1/0").evaluate(0., 0.).is_finite());
+        assert!(parse("1/(x - x)").evaluate(1., 0.).is_finite());
+    }
+
+    #[test]
+    fn invalid_input_gives_an_error_where_it_went_wrong() {
+        let error_at = |input: &str| Expression::parse(input).map(|_| ()).unwrap_err().position;
+
+        assert_eq!(error_at(""), 0); // nothing to parse
+        assert_eq!(error_at("x +"), 3); // missing operand at the end
+        assert_eq!(error_at("(x + 1"), 6); // missing ')'
+        assert_eq!(error_at("x + 1)"), 5); // extra ')'
+        assert_eq!(error_at("x + foo"), 4); // unknown name
+        assert_eq!(error_at("x % 2"), 2); // unknown character
+        assert_eq!(error_at("1..2"), 0); // invalid number
+        assert_eq!(error_at("*x"), 0);
+    }
+
+    #[test]
+    fn scientific_notation_is_rejected() {
+        assert!(Expression::parse("1e5").is_err());
+        assert!(Expression::parse("2.5E3").is_err());
+        assert_evaluates("2e", |_, _| 2.*E); // e after a number is still the constant
+        assert_evaluates("2e^x", |x, _| 2.*E.powf(x));
+    }
+
+    #[test]
+    fn display_parses_back_to_the_same_values() {
+        let inputs = [
+            "x + y", "x - (y - 1)", "x - y + 1", "-(x + y)", "-x^2", "(-x)^2", "(x^2)^y", "x^y^2", "2^-x",
+            "xy", "2x", "2xy", "x*2", "x/(y*2)", "(x/y)/2", "x*(-1)", "-2x", "(-2)^x", "2(x + 1)", "e^x", "2e",
+            "sin(x)^2", "cosx^2", "abs(x - y)/sqrt(x^2 + y^2)", "ln(e)", "-(-x)", "x - -y", "pi*x^2",
+        ];
+
+        for input in inputs {
+            let expression = parse(input);
+            let text = expression.to_string();
+            let reparsed = parse(&text);
+
+            for (x, y) in POINTS {
+                let (value, reparsed_value) = (expression.evaluate(x, y), reparsed.evaluate(x, y));
+                assert!(close(value, reparsed_value), "\"{}\" shows as \"{}\", which gives {} instead of {} at ({}, {})", input, text, reparsed_value, value, x, y);
+            }
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use bevy::prelude::*;
+
+use crate::{Gradient, GameState, Level};
+use crate::expression::{Expression, Variable};
+
+use crate::constants::{
+    FIELD_SCALE,
+    ANALYSIS_SAMPLES,
+    ANALYSIS_STEP,
+    ANALYSIS_MARGIN,
+    CONSERVATIVE_TOLERANCE,
+    ANALYSIS_TEXT_COLOR,
+    PARSE_ERROR_TEXT_COLOR,
+    BUTTON_HEIGHT,
+    BUTTON_SPACING,
+};
+
+#[derive(Resource)]
+/// Curl and divergence of the current field, for showing whether it is conservative. Values are for the field
+/// as the player wrote it (without FIELD_SCALE)
+pub struct FieldAnalysis {
+    pub curl: Expression, // symbolic curl, dQ/dx - dP/dy for the field (P, Q)
+    pub divergence: Expression, // symbolic divergence, dP/dx + dQ/dy
+    pub max_curl: f32, // largest estimated |curl| over the level
+    pub max_divergence: f32, // largest estimated |divergence| over the level
+    pub conservative: bool, // whether the field is conservative (curl is 0 everywhere)
+}
+
+impl FieldAnalysis {
+    /// New method. The empty field has no curl or divergence
+    pub fn new() -> Self {
+        Self {
+            curl: Expression::Number(0.),
+            divergence: Expression::Number(0.),
+            max_curl: 0.,
+            max_divergence: 0.,
+            conservative: true,
+        }
+    }
+}
+
+#[derive(Component)]
+/// Struct to label the text of the field analysis panel
+pub struct FieldAnalysisText;
+
+/// Symbolic curl of the field (P, Q), dQ/dx - dP/dy
+pub fn symbolic_curl(x_component: &Expression, y_component: &Expression) -> Expression {
+    Expression::Subtract(
+        Box::new(y_component.derivative(Variable::X)),
+        Box::new(x_component.derivative(Variable::Y)),
+    ).simplify()
+}
+
+/// Symbolic divergence of the field (P, Q), dP/dx + dQ/dy
+pub fn symbolic_divergence(x_component: &Expression, y_component: &Expression) -> Expression {
+    Expression::Add(
+        Box::new(x_component.derivative(Variable::X)),
+        Box::new(y_component.derivative(Variable::Y)),
+    ).simplify()
+}
+
+/// Estimate the partial derivatives (dP/dx, dP/dy, dQ/dx, dQ/dy) of a field at a point with central differences
+pub fn numeric_partials(field: impl Fn(f32, f32) -> (f32, f32), x: f32, y: f32) -> (f32, f32, f32, f32) {
+    let (right, left) = (field(x + ANALYSIS_STEP, y), field(x - ANALYSIS_STEP, y));
+    let (up, down) = (field(x, y + ANALYSIS_STEP), field(x, y - ANALYSIS_STEP));
+
+    (
+        (right.0 - left.0) / (2. * ANALYSIS_STEP), // dP/dx
+        (up.0 - down.0) / (2. * ANALYSIS_STEP), // dP/dy
+        (right.1 - left.1) / (2. * ANALYSIS_STEP), // dQ/dx
+        (up.1 - down.1) / (2. * ANALYSIS_STEP), // dQ/dy
+    )
+}
+
+/// Edges (min_x, max_x, min_y, max_y) of the part of a level the field is analyzed over: its start, end and gas cans
+/// with ANALYSIS_MARGIN around them. Fixed by the level so the analysis doesn't depend on where the camera is
+fn level_region(level: &Level) -> (f32, f32, f32, f32) {
+    let points = level.gas_locations.iter().chain([&level.start_location, &level.end_location]);
+
+    points.fold(
+        (f32::INFINITY, f32::NEG_INFINITY, f32::INFINITY, f32::NEG_INFINITY),
+        |(min_x, max_x, min_y, max_y), (x, y)| (
+            min_x.min(x - ANALYSIS_MARGIN),
+            max_x.max(x + ANALYSIS_MARGIN),
+            min_y.min(y - ANALYSIS_MARGIN),
+            max_y.max(y + ANALYSIS_MARGIN),
+        ),
+    )
+}
+
+/// Analyze a gradient over a level. Curl and divergence are found symbolically, then estimated numerically from
+/// Gradient::x and Gradient::y on a grid of points. The field is conservative if it came from a potential or if its
+/// curl simplifies to 0. A curl that simplifies to any other number means it isn't. Otherwise simplifying couldn't
+/// settle it, and the field is conservative if the estimated curl is small next to the field's derivatives
+/// everywhere sampled
+pub fn analyze_field(gradient: &Gradient, level: &Level) -> FieldAnalysis {
+    let (x_component, y_component) = (gradient.x_expression(), gradient.y_expression());
+    let (min_x, max_x, min_y, max_y) = level_region(level);
+
+    let curl = symbolic_curl(&x_component, &y_component);
+    let divergence = symbolic_divergence(&x_component, &y_component);
+
+    let field = |x: f32, y: f32| (gradient.x(x, y) / FIELD_SCALE, gradient.y(x, y) / FIELD_SCALE); // field as the player wrote it
+
+    let mut max_curl: f32 = 0.;
+    let mut max_divergence: f32 = 0.;
+    let mut max_partial: f32 = 0.; // size of the field's derivatives, to judge how small the curl is
+
+    for i in 0..ANALYSIS_SAMPLES {
+        for j in 0..ANALYSIS_SAMPLES {
+            let x = min_x + (i as f32) * (max_x - min_x) / ((ANALYSIS_SAMPLES as f32) - 1.);
+            let y = min_y + (j as f32) * (max_y - min_y) / ((ANALYSIS_SAMPLES as f32) - 1.);
+
+            let (p_x, p_y, q_x, q_y) = numeric_partials(field, x, y);
+
+            max_curl = max_curl.max((q_x - p_y).abs());
+            max_divergence = max_divergence.max((p_x + q_y).abs());
+            max_partial = max_partial.max(p_x.abs()).max(p_y.abs()).max(q_x.abs()).max(q_y.abs());
+        }
+    }
+
+    let constant_curl = !curl.contains_variable(Variable::X) && !curl.contains_variable(Variable::Y);
+
+    let conservative = if gradient.potential.is_some() {
+        true // gradient of a potential always has no curl
+    } else if constant_curl {
+        curl.evaluate(0., 0.) == 0. // symbolic curl settles it
+    } else {
+        max_curl <= CONSERVATIVE_TOLERANCE * max_partial
+    };
+
+    FieldAnalysis {
+        curl,
+        divergence,
+        max_curl,
+        max_divergence,
+        conservative,
+    }
+}
+
+/// Reanalyze the field whenever it or the level changes
+pub fn field_analysis_update(
+    gradient: Query<(&Gradient, ChangeTrackers<Gradient>)>,
+    game_state: Query<&GameState>,
+    mut analyzed_level: Local<Option<u32>>, // level the current analysis is for
+    mut analysis: ResMut<FieldAnalysis>,
+) {
+    let (gradient, gradient_tracker) = gradient.single(); // should only be 1 gradient
+    let game_state = game_state.single();
+
+    let level = match game_state.level_info.get(game_state.current_level as usize) {
+        Some(level) => level,
+        None => return, // levels haven't loaded yet
+    };
+
+    if !gradient_tracker.is_changed() && *analyzed_level == Some(game_state.current_level) {
+        return
+    }
+
+    *analysis = analyze_field(gradient, level);
+    *analyzed_level = Some(game_state.current_level);
+}
+
+/// Spawn the field analysis panel, below the simulate button
+fn field_analysis_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let style = TextStyle {
+        font: asset_server.load("fonts/tahoma.ttf"),
+        font_size: 18.0,
+        color: ANALYSIS_TEXT_COLOR,
+    };
+
+    commands
+        .spawn(TextBundle::from_sections([
+            TextSection::new("", style.clone()), // curl
+            TextSection::new("", style.clone()), // divergence
+            TextSection::new("", style), // conservative or not
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(BUTTON_SPACING),
+                top: Val::Px(BUTTON_HEIGHT + 3.*BUTTON_SPACING),
+                ..default()
+            },
+            ..default()
+        }))
+        .insert(FieldAnalysisText);
+}
+
+/// Update the field analysis panel
+fn field_analysis_text_system(
+    mut text_query: Query<&mut Text, With<FieldAnalysisText>>,
+    analysis: Res<FieldAnalysis>,
+    game_state: Query<&GameState>,
+) {
+    let game_state = game_state.single();
+    let mut text = text_query.single_mut();
+
+    // levels may not have loaded yet
+    let required = game_state.level_info.get(game_state.current_level as usize).map(|level| level.require_conservative).unwrap_or(false);
+
+    text.sections[0].value = format!("curl = {}   (max |curl| ≈ {:.3})\n", analysis.curl, analysis.max_curl);
+    text.sections[1].value = format!("div = {}   (max |div| ≈ {:.3})\n", analysis.divergence, analysis.max_divergence);
+
+    text.sections[2].value = match (analysis.conservative, required) {
+        (true, _) => String::from("Conservative: yes"),
+        (false, false) => String::from("Conservative: no"),
+        (false, true) => String::from("Conservative: no (this level needs a conservative field)"),
+    };
+    text.sections[2].style.color = match (analysis.conservative, required) {
+        (false, true) => PARSE_ERROR_TEXT_COLOR, // level can't be finished with this field
+        _ => ANALYSIS_TEXT_COLOR,
+    };
+}
+
+/// Plugin for analyzing the current field and showing the results
+pub struct FieldAnalysisPlugin;
+
+impl Plugin for FieldAnalysisPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FieldAnalysis::new());
+        app.add_startup_system(field_analysis_setup);
+        app.add_system(field_analysis_update);
+        app.add_system(field_analysis_text_system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gradient_field::FieldNode;
+    use crate::integrator::Integrator;
+
+    /// Level from (-15, -15) to (0, 0) with no gas cans
+    fn level() -> Level {
+        Level {
+            id: String::from("test"),
+            level_number: 0,
+            start_location: (-15., -15.),
+            end_location: (0., 0.),
+            x_functions: Vec::new(),
+            y_functions: Vec::new(),
+            gas_locations: Vec::new(),
+            tick_time: 0.01,
+            require_conservative: true,
+            integrator: Integrator::default(),
+            solution: None,
+            par: None,
+        }
+    }
+
+    /// Field built from one function in each component
+    fn field(x: &str, y: &str) -> Gradient {
+        Gradient {
+            x_component: Some(FieldNode::Function { id: 0, function: Expression::parse(x).unwrap() }),
+            y_component: Some(FieldNode::Function { id: 0, function: Expression::parse(y).unwrap() }),
+            potential: None,
+        }
+    }
+
+    #[test]
+    fn small_constant_curl_is_not_conservative() {
+        let analysis = analyze_field(&field("y/300", "0"), &level());
+        assert_eq!(analysis.curl.evaluate(0., 0.), -1. / 300.);
+        assert!(!analysis.conservative);
+    }
+
+    #[test]
+    fn curl_that_simplifies_to_zero_is_conservative() {
+        assert!(analyze_field(&field("y", "x"), &level()).conservative);
+        assert!(analyze_field(&field("2x", "cos(y)"), &level()).conservative);
+    }
+
+    #[test]
+    fn curl_that_changes_over_the_level_is_estimated() {
+        assert!(!analyze_field(&field("x*y", "0"), &level()).conservative); // curl is -x
+        assert!(analyze_field(&field("x*y", "x^2/2"), &level()).conservative); // curl is x - x
+    }
+}
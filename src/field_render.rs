@@ -0,0 +1,450 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::{Gradient, GameState, LoadingState};
+use crate::gradient_field::GradientArrow;
+use crate::camera::VisibleRect;
+use crate::integrator::Integrator;
+use crate::heatmap::{MagnitudeRange, heatmap_update};
+
+use crate::constants::{
+    MOVEMENT_SCALE_PER_SECOND,
+    STREAMLINE_SEEDS_X,
+    STREAMLINE_SEEDS_Y,
+    STREAMLINE_STEPS,
+    STREAMLINE_STEP_SIZE,
+    STREAMLINE_WIDTH,
+    PARTICLE_COUNT,
+    PARTICLE_SIZE,
+    PARTICLE_LIFETIME,
+    LIC_HEIGHT,
+    LIC_LENGTH,
+};
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+/// enum to store how the gradient field is drawn
+pub enum FieldRenderMode {
+    Arrows, // grid of arrows pointing along the field
+    Streamlines, // curves following the field, seeded across the window
+    Particles, // particles moving through the field the same way the player would
+    Texture, // line integral convolution texture, noise smeared along the field
+}
+
+impl FieldRenderMode {
+    /// Mode the render mode button switches to next
+    pub fn next(&self) -> Self {
+        match self {
+            FieldRenderMode::Arrows => FieldRenderMode::Streamlines,
+            FieldRenderMode::Streamlines => FieldRenderMode::Particles,
+            FieldRenderMode::Particles => FieldRenderMode::Texture,
+            FieldRenderMode::Texture => FieldRenderMode::Arrows,
+        }
+    }
+
+    /// Text of the render mode button, naming the mode it switches to
+    pub fn button_text(&self) -> &'static str {
+        match self.next() {
+            FieldRenderMode::Arrows => "Arrows",
+            FieldRenderMode::Streamlines => "Streams",
+            FieldRenderMode::Particles => "Particles",
+            FieldRenderMode::Texture => "Texture",
+        }
+    }
+}
+
+#[derive(Component)]
+/// Struct to label one segment of a streamline
+pub struct StreamlineSegment {
+    pub index: u32, // which segment this is, across all streamlines
+}
+
+#[derive(Component)]
+/// Struct to label a particle moving through the field
+pub struct FieldParticle {
+    pub index: u32, // which particle this is
+}
+
+#[derive(Component)]
+/// Struct to label the sprite showing the line integral convolution texture
+pub struct FieldTexture;
+
+#[derive(Resource)]
+/// Positions and ages of the particles moving through the field
+pub struct FieldParticles {
+    pub positions: Vec<(f32, f32)>, // position of each particle
+    pub ages: Vec<f32>, // seconds since each particle was last respawned
+    pub spawned: u32, // number of respawns so far, used to pick the next respawn location
+}
+
+impl FieldParticles {
+    /// New method. Particles start at the origin and are spread over the window the first time they move
+    pub fn new() -> Self {
+        Self {
+            positions: vec![(0., 0.); PARTICLE_COUNT as usize],
+            ages: (0..PARTICLE_COUNT).map(|index| PARTICLE_LIFETIME * (1. + index as f32 / PARTICLE_COUNT as f32)).collect(), // past their lifetime, so all respawn, staggered so they don't all respawn together after
+            spawned: 0,
+        }
+    }
+
+    /// Move a particle to a new spot in the visible rectangle
+    fn respawn(&mut self, index: usize, rect: &VisibleRect) {
+        let x = rect.min_x + noise(2 * self.spawned) * rect.width();
+        let y = rect.min_y + noise(2 * self.spawned + 1) * rect.height();
+
+        self.positions[index] = (x, y);
+        self.ages[index] = (self.ages[index] - PARTICLE_LIFETIME).clamp(0., PARTICLE_LIFETIME); // keep the stagger
+        self.spawned = self.spawned.wrapping_add(1);
+    }
+}
+
+/// Pseudo-random number from 0 to 1 for an integer. The same integer always gives the same number
+fn noise(n: u32) -> f32 {
+    let mut hash = n.wrapping_mul(0x9E37_79B1);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x85EB_CA77);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xC2B2_AE3D);
+    hash ^= hash >> 16;
+
+    hash as f32 / u32::MAX as f32
+}
+
+/// Direction of the field at a point as a unit vector, or (0, 0) where the field is 0
+fn field_direction(gradient: &Gradient, x: f32, y: f32) -> (f32, f32) {
+    let (field_x, field_y) = (gradient.x(x, y), gradient.y(x, y));
+    let magnitude = (field_x.powi(2) + field_y.powi(2)).sqrt();
+
+    if magnitude > f32::EPSILON {
+        (field_x / magnitude, field_y / magnitude)
+    } else {
+        (0., 0.)
+    }
+}
+
+/// Follow the field from a seed point for up to the given number of steps each way, moving step_size world units
+/// per step. Returns the points from the far end behind the seed to the far end ahead of it.
+/// Stops early in either direction where the field vanishes
+pub fn trace_streamline(gradient: &Gradient, seed: (f32, f32), step_size: f32, steps: u32) -> Vec<(f32, f32)> {
+    let direction = |x: f32, y: f32| field_direction(gradient, x, y);
+
+    let trace = |step_size: f32| {
+        let mut points = Vec::new();
+        let mut position = seed;
+
+        for _ in 0..steps {
+            let next = Integrator::Rk4.step(direction, position, step_size);
+            if ((next.0 - position.0).powi(2) + (next.1 - position.1).powi(2)).sqrt() < 0.5 * step_size.abs() { // stalled at a point where the field is 0
+                break;
+            }
+            points.push(next);
+            position = next;
+        }
+
+        points
+    };
+
+    let mut points = trace(-step_size);
+    points.reverse();
+    points.push(seed);
+    points.extend(trace(step_size));
+
+    points
+}
+
+/// RGBA pixels of a line integral convolution texture of the field over the visible rectangle, with the first row at the top. Each pixel averages white noise along the field
+/// line through it, so the texture is streaked along the field. Pixels are colored by where the field's magnitude falls in the range
+pub fn line_integral_convolution(gradient: &Gradient, range: &MagnitudeRange, rect: &VisibleRect, width: u32, height: u32) -> Vec<u8> {
+    let pixel_size = rect.height() / height as f32; // world units per pixel
+    let (width, height) = (width as usize, height as usize);
+
+    // field at each pixel center, found once since every pixel's field line passes through many pixels
+    let mut directions = Vec::with_capacity(width * height);
+    let mut magnitudes = Vec::with_capacity(width * height);
+    for row in 0..height {
+        for column in 0..width {
+            let x = rect.min_x + (column as f32 + 0.5) * pixel_size;
+            let y = rect.max_y - (row as f32 + 0.5) * pixel_size;
+
+            directions.push(field_direction(gradient, x, y));
+            magnitudes.push(gradient.magnitude(x, y));
+        }
+    }
+
+    let white_noise: Vec<f32> = (0..width * height).map(|index| noise(index as u32)).collect();
+
+    let mut data = Vec::with_capacity(width * height * 4);
+    for row in 0..height {
+        for column in 0..width {
+            let mut total = white_noise[row * width + column];
+            let mut samples = 1.;
+
+            for sign in [1., -1.] { // follow the field line forwards then backwards, a pixel at a time
+                let (mut x, mut y) = (column as f32 + 0.5, row as f32 + 0.5); // position in pixels
+
+                for _ in 0..LIC_LENGTH {
+                    let direction = directions[(y as usize) * width + (x as usize)];
+                    if direction == (0., 0.) {
+                        break;
+                    }
+
+                    x += sign * direction.0;
+                    y -= sign * direction.1; // rows go down the screen
+
+                    if x < 0. || y < 0. || x >= width as f32 || y >= height as f32 {
+                        break;
+                    }
+
+                    total += white_noise[(y as usize) * width + (x as usize)];
+                    samples += 1.;
+                }
+            }
+
+            let intensity = total / samples;
+            let color = range.color(magnitudes[row * width + column], 0.1 + 0.6 * intensity).as_rgba_f32();
+
+            data.extend(color.iter().map(|channel| (channel * 255.) as u8));
+        }
+    }
+
+    data
+}
+
+/// Spawn the streamline segments, particles and texture sprite. They start invisible since arrows are shown first
+fn field_render_setup(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    for index in 0..STREAMLINE_SEEDS_X * STREAMLINE_SEEDS_Y * 2 * STREAMLINE_STEPS {
+        commands
+            .spawn(SpriteBundle {
+                transform: Transform::from_xyz(0., 0., -0.03), // below level objects
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(StreamlineSegment { index });
+    }
+
+    for index in 0..PARTICLE_COUNT {
+        commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(Vec2::splat(PARTICLE_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(0., 0., -0.02), // below level objects
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(FieldParticle { index });
+    }
+
+    let texture = images.add(Image::new_fill(
+        Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+    )); // filled in when the texture mode is first shown
+
+    commands
+        .spawn(SpriteBundle {
+            texture,
+            transform: Transform::from_xyz(0., 0., -0.05), // below everything else
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(FieldTexture);
+}
+
+/// Show the arrows and texture only in their modes, and hide the streamlines and particles when switching away from them
+fn render_mode_visibility_system(
+    render_mode: Res<State<FieldRenderMode>>,
+    mut arrows: Query<&mut Visibility, (With<GradientArrow>, Without<StreamlineSegment>, Without<FieldParticle>, Without<FieldTexture>)>,
+    mut segments: Query<&mut Visibility, (With<StreamlineSegment>, Without<GradientArrow>, Without<FieldParticle>, Without<FieldTexture>)>,
+    mut particles: Query<&mut Visibility, (With<FieldParticle>, Without<GradientArrow>, Without<StreamlineSegment>, Without<FieldTexture>)>,
+    mut texture: Query<&mut Visibility, (With<FieldTexture>, Without<GradientArrow>, Without<StreamlineSegment>, Without<FieldParticle>)>,
+) {
+    if !render_mode.is_changed() { // only need to update when mode changes
+        return
+    }
+
+    let mode = render_mode.current();
+
+    for mut visibility in arrows.iter_mut() {
+        visibility.is_visible = *mode == FieldRenderMode::Arrows;
+    }
+    for mut visibility in texture.iter_mut() {
+        visibility.is_visible = *mode == FieldRenderMode::Texture;
+    }
+
+    // segments and particles that are in use are shown by their own systems
+    if *mode != FieldRenderMode::Streamlines {
+        for mut visibility in segments.iter_mut() {
+            visibility.is_visible = false;
+        }
+    }
+    if *mode != FieldRenderMode::Particles {
+        for mut visibility in particles.iter_mut() {
+            visibility.is_visible = false;
+        }
+    }
+}
+
+/// Trace streamlines from a grid of seeds whenever the field changes, the camera moves, or streamlines are shown
+fn streamline_update(
+    render_mode: Res<State<FieldRenderMode>>,
+    gradient: Query<(&Gradient, ChangeTrackers<Gradient>)>,
+    rect: Res<VisibleRect>,
+    range: Res<MagnitudeRange>,
+    mut segments: Query<(&StreamlineSegment, &mut Sprite, &mut Transform, &mut Visibility)>,
+) {
+    let (gradient, gradient_tracker) = gradient.single(); // should only be 1 gradient
+
+    if *render_mode.current() != FieldRenderMode::Streamlines || !(gradient_tracker.is_changed() || rect.is_changed() || render_mode.is_changed()) {
+        return
+    }
+
+    let mut lines = Vec::new(); // (start, end, magnitude) of each segment
+    for i in 0..STREAMLINE_SEEDS_X {
+        for j in 0..STREAMLINE_SEEDS_Y {
+            let seed = (
+                rect.min_x + (i as f32 + 0.5) * rect.width() / STREAMLINE_SEEDS_X as f32,
+                rect.min_y + (j as f32 + 0.5) * rect.height() / STREAMLINE_SEEDS_Y as f32,
+            );
+
+            let points = trace_streamline(gradient, seed, STREAMLINE_STEP_SIZE, STREAMLINE_STEPS);
+            for pair in points.windows(2) {
+                let middle = ((pair[0].0 + pair[1].0) / 2., (pair[0].1 + pair[1].1) / 2.);
+                lines.push((pair[0], pair[1], gradient.magnitude(middle.0, middle.1)));
+            }
+        }
+    }
+
+    for (segment, mut sprite, mut transform, mut visibility) in segments.iter_mut() {
+        match lines.get(segment.index as usize) {
+            Some((start, end, magnitude)) => {
+                let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+
+                sprite.custom_size = Some(Vec2::new((dx.powi(2) + dy.powi(2)).sqrt(), STREAMLINE_WIDTH));
+                sprite.color = range.color(*magnitude, 0.7);
+
+                transform.translation.x = (start.0 + end.0) / 2.;
+                transform.translation.y = (start.1 + end.1) / 2.;
+                transform.rotation = Quat::from_rotation_z(dy.atan2(dx));
+
+                visibility.is_visible = true;
+            },
+            None => visibility.is_visible = false, // streamlines stopped early
+        }
+    }
+}
+
+/// Move particles through the field the same way the player moves, respawning them when they get old or leave the view
+fn particle_system(
+    render_mode: Res<State<FieldRenderMode>>,
+    gradient: Query<&Gradient>,
+    game_state: Query<&GameState>,
+    rect: Res<VisibleRect>,
+    time: Res<Time>,
+    mut field_particles: ResMut<FieldParticles>,
+) {
+    if *render_mode.current() != FieldRenderMode::Particles {
+        return
+    }
+
+    let gradient = gradient.single(); // should only be 1 gradient
+    let game_state = game_state.single();
+    let level = &game_state.level_info[game_state.current_level as usize];
+
+    let dt = level.tick_time * MOVEMENT_SCALE_PER_SECOND * time.delta_seconds(); // time to move through the field this frame
+
+    for index in 0..PARTICLE_COUNT as usize {
+        let position = field_particles.positions[index];
+        field_particles.positions[index] = level.integrator.step(|x, y| (gradient.x(x, y), gradient.y(x, y)), position, dt);
+        field_particles.ages[index] += time.delta_seconds();
+
+        let (x, y) = field_particles.positions[index];
+        if field_particles.ages[index] > PARTICLE_LIFETIME || !rect.contains(x, y) {
+            field_particles.respawn(index, &rect);
+        }
+    }
+}
+
+/// Place the particles and color them by the field's magnitude where they are
+fn particle_display_system(
+    render_mode: Res<State<FieldRenderMode>>,
+    gradient: Query<&Gradient>,
+    range: Res<MagnitudeRange>,
+    field_particles: Res<FieldParticles>,
+    mut particles: Query<(&FieldParticle, &mut Sprite, &mut Transform, &mut Visibility)>,
+) {
+    if *render_mode.current() != FieldRenderMode::Particles {
+        return
+    }
+
+    let gradient = gradient.single(); // should only be 1 gradient
+
+    for (particle, mut sprite, mut transform, mut visibility) in particles.iter_mut() {
+        let (x, y) = field_particles.positions[particle.index as usize];
+        let age = field_particles.ages[particle.index as usize] / PARTICLE_LIFETIME;
+
+        let mut color = range.color(gradient.magnitude(x, y), 0.7);
+        color.set_a((age * std::f32::consts::PI).sin()); // fade in after respawning and out before the next
+        sprite.color = color;
+
+        transform.translation.x = x;
+        transform.translation.y = y;
+
+        visibility.is_visible = true;
+    }
+}
+
+/// Redraw the line integral convolution texture whenever the field changes, the camera moves, or the texture is shown
+fn texture_update(
+    render_mode: Res<State<FieldRenderMode>>,
+    gradient: Query<(&Gradient, ChangeTrackers<Gradient>)>,
+    rect: Res<VisibleRect>,
+    range: Res<MagnitudeRange>,
+    mut images: ResMut<Assets<Image>>,
+    mut texture: Query<(&Handle<Image>, &mut Sprite, &mut Transform), With<FieldTexture>>,
+) {
+    let (gradient, gradient_tracker) = gradient.single(); // should only be 1 gradient
+
+    if *render_mode.current() != FieldRenderMode::Texture || !(gradient_tracker.is_changed() || rect.is_changed() || render_mode.is_changed()) {
+        return
+    }
+
+    let (handle, mut sprite, mut transform) = texture.single_mut();
+
+    let height = LIC_HEIGHT;
+    let width = ((LIC_HEIGHT as f32) * rect.width() / rect.height()).round().max(1.) as u32; // square pixels
+
+    if let Some(image) = images.get_mut(handle) {
+        *image = Image::new(
+            Extent3d { width, height, depth_or_array_layers: 1 },
+            TextureDimension::D2,
+            line_integral_convolution(gradient, &range, &rect, width, height),
+            TextureFormat::Rgba8UnormSrgb,
+        );
+    }
+
+    sprite.custom_size = Some(Vec2::new(rect.width(), rect.height())); // cover the view
+    (transform.translation.x, transform.translation.y) = rect.center();
+}
+
+/// Plugin for the alternative ways of drawing the field
+pub struct FieldRenderPlugin;
+
+impl Plugin for FieldRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state(FieldRenderMode::Arrows); // start with arrows
+        app.insert_resource(FieldParticles::new());
+        app.add_startup_system(field_render_setup);
+        app.add_system(render_mode_visibility_system);
+        app.add_system(streamline_update.after(render_mode_visibility_system).after(heatmap_update)); // colors need the new magnitude range
+        app.add_system(texture_update.after(heatmap_update));
+        app.add_system_set(
+            SystemSet::on_update(LoadingState::Loaded) // particles move like the player, which needs level info
+                .with_system(particle_system)
+                .with_system(particle_display_system.after(particle_system).after(render_mode_visibility_system).after(heatmap_update))
+        );
+    }
+}
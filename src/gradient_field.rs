@@ -1,133 +1,486 @@
 use std::f32::consts::PI;
+use std::fmt;
 
 use bevy::{
     prelude::*,
     asset::AssetServer
 };
-
-use crate::constants::{NUM_ARROWS_X, NUM_ARROWS_Y, BASE_ARROW_SCALE, VERTICAL_WINDOW_HEIGHT, EXPECTED_MAX_ARROW_SCALE, FIELD_SCALE};
+use serde::{Serialize, Deserialize};
+
+use crate::expression::{Expression, Variable, safe_divide};
+use crate::field_render::FieldRenderMode;
+use crate::ui::ExpressionInput;
+use crate::heatmap::MagnitudeRange;
+use crate::camera::VisibleRect;
+
+use crate::constants::{
+    NUM_ARROWS_X,
+    NUM_ARROWS_Y,
+    MIN_ARROWS,
+    MAX_ARROWS,
+    ARROW_DENSITY_STEP,
+    BASE_ARROW_SCALE,
+    ARROW_ROOT,
+    ARROW_LOG_SHARPNESS,
+    VERTICAL_WINDOW_HEIGHT,
+    EXPECTED_MAX_ARROW_SCALE,
+    FIELD_SCALE,
+};
 
 /// This enum represents the valid operations between parts of the gradient function 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GradientOperation {
     Add,
+    Subtract,
     Multiply,
+    Divide,
+    Power,
+    ComposeX, // substitute everything before into x of the new function
+    ComposeY, // substitute everything before into y of the new function
 }
 
 impl GradientOperation {
-    /// initializes enum for consistency 
-    pub fn new() -> Self {
-        GradientOperation::Add
+    /// Every operation, in the order shown in the operation selector
+    pub const ALL: [GradientOperation; 7] = [
+        GradientOperation::Add,
+        GradientOperation::Subtract,
+        GradientOperation::Multiply,
+        GradientOperation::Divide,
+        GradientOperation::Power,
+        GradientOperation::ComposeX,
+        GradientOperation::ComposeY,
+    ];
+
+    /// Short label of the operation for the operation selector
+    pub fn label(&self) -> &'static str {
+        match self {
+            GradientOperation::Add => "+",
+            GradientOperation::Subtract => "−",
+            GradientOperation::Multiply => "×",
+            GradientOperation::Divide => "÷",
+            GradientOperation::Power => "^",
+            GradientOperation::ComposeX => "Into x",
+            GradientOperation::ComposeY => "Into y",
+        }
+    }
+
+    /// Whether the operation substitutes everything before it into the new function
+    fn is_composition(&self) -> bool {
+        matches!(self, GradientOperation::ComposeX | GradientOperation::ComposeY)
+    }
+
+    /// Binding strength of the operation. Operations that bind tighter are evaluated first, as in "a + b * c". Uses the
+    /// same scale as Expression::precedence, since functions from the buttons are displayed inside the tree
+    fn precedence(&self) -> u8 {
+        match self {
+            GradientOperation::ComposeX | GradientOperation::ComposeY => 0, // composing always takes everything before it
+            GradientOperation::Add | GradientOperation::Subtract => 1,
+            GradientOperation::Multiply | GradientOperation::Divide => 2,
+            GradientOperation::Power => 4, // above negation (3), so "-x" as a base or exponent gets parentheses
+        }
+    }
+
+    /// Whether repeated uses of the operation group from the right, as in "a ^ b ^ c" = "a ^ (b ^ c)"
+    fn right_associative(&self) -> bool {
+        matches!(self, GradientOperation::Power)
+    }
+
+    /// Apply the operation to already evaluated operands. Composition is handled while evaluating the tree instead
+    fn apply(&self, lhs: f32, rhs: f32) -> f32 {
+        match self {
+            GradientOperation::Add => lhs + rhs,
+            GradientOperation::Subtract => lhs - rhs,
+            GradientOperation::Multiply => lhs * rhs,
+            GradientOperation::Divide => safe_divide(lhs, rhs),
+            GradientOperation::Power => lhs.powf(rhs),
+            GradientOperation::ComposeX | GradientOperation::ComposeY => rhs,
+        }
+    }
+}
+
+/// Node in the expression tree that the player builds for one component of the gradient 
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldNode {
+    Function {
+        id: u32, // function id (for current level)
+        function: Expression, // function itself
+    },
+    Operation {
+        operation: GradientOperation, // operation combining the two sides
+        lhs: Box<FieldNode>, // everything the operation applies to on the left
+        rhs: Box<FieldNode>, // everything the operation applies to on the right
+    },
+    Group(Box<FieldNode>), // terms grouped together by the player, as if in parentheses
+    OpenGroup(Box<FieldNode>), // group the player has opened but not closed yet. New functions go inside it
+}
+
+impl FieldNode {
+    /// Evaluate this part of the tree at the point (x, y)
+    pub fn evaluate(&self, x: f32, y: f32) -> f32 {
+        match self {
+            FieldNode::Function { function, .. } => function.evaluate(x, y),
+            FieldNode::Operation { operation: GradientOperation::ComposeX, lhs, rhs } => rhs.evaluate(lhs.evaluate(x, y), y), // substitute into x
+            FieldNode::Operation { operation: GradientOperation::ComposeY, lhs, rhs } => rhs.evaluate(x, lhs.evaluate(x, y)), // substitute into y
+            FieldNode::Operation { operation, lhs, rhs } => operation.apply(lhs.evaluate(x, y), rhs.evaluate(x, y)),
+            FieldNode::Group(inner) | FieldNode::OpenGroup(inner) => inner.evaluate(x, y),
+        }
+    }
+
+    /// Each function in this part of the tree, in order, with its value where it is used when evaluating the tree at
+    /// the point (x, y). Functions composed into x or y are evaluated at the substituted point
+    pub fn terms(&self, x: f32, y: f32) -> Vec<(String, f32)> {
+        match self {
+            FieldNode::Function { function, .. } => vec![(function.to_string(), function.evaluate(x, y))],
+            FieldNode::Operation { operation: GradientOperation::ComposeX, lhs, rhs } => {
+                let mut terms = lhs.terms(x, y);
+                terms.extend(rhs.terms(lhs.evaluate(x, y), y));
+                terms
+            },
+            FieldNode::Operation { operation: GradientOperation::ComposeY, lhs, rhs } => {
+                let mut terms = lhs.terms(x, y);
+                terms.extend(rhs.terms(x, lhs.evaluate(x, y)));
+                terms
+            },
+            FieldNode::Operation { lhs, rhs, .. } => {
+                let mut terms = lhs.terms(x, y);
+                terms.extend(rhs.terms(x, y));
+                terms
+            },
+            FieldNode::Group(inner) | FieldNode::OpenGroup(inner) => inner.terms(x, y),
+        }
+    }
+
+    /// Number of functions in this part of the tree
+    pub fn function_count(&self) -> u32 {
+        match self {
+            FieldNode::Function { .. } => 1,
+            FieldNode::Operation { lhs, rhs, .. } => lhs.function_count() + rhs.function_count(),
+            FieldNode::Group(inner) | FieldNode::OpenGroup(inner) => inner.function_count(),
+        }
+    }
+
+    /// Ids of the functions in this part of the tree, in order
+    pub fn function_ids(&self) -> Vec<u32> {
+        match self {
+            FieldNode::Function { id, .. } => vec![*id],
+            FieldNode::Operation { lhs, rhs, .. } => {
+                let mut ids = lhs.function_ids();
+                ids.extend(rhs.function_ids());
+                ids
+            },
+            FieldNode::Group(inner) | FieldNode::OpenGroup(inner) => inner.function_ids(),
+        }
+    }
+
+    /// Number of operations combining functions in this part of the tree
+    pub fn operation_count(&self) -> u32 {
+        match self {
+            FieldNode::Function { .. } => 0,
+            FieldNode::Operation { lhs, rhs, .. } => 1 + lhs.operation_count() + rhs.operation_count(),
+            FieldNode::Group(inner) | FieldNode::OpenGroup(inner) => inner.operation_count(),
+        }
+    }
+
+    /// Convert this part of the tree into a single expression
+    pub fn to_expression(&self) -> Expression {
+        match self {
+            FieldNode::Function { function, .. } => function.clone(),
+            FieldNode::Operation { operation, lhs, rhs } => {
+                let (lhs, rhs) = (lhs.to_expression(), rhs.to_expression());
+                match operation {
+                    GradientOperation::Add => Expression::Add(Box::new(lhs), Box::new(rhs)),
+                    GradientOperation::Subtract => Expression::Subtract(Box::new(lhs), Box::new(rhs)),
+                    GradientOperation::Multiply => Expression::Multiply(Box::new(lhs), Box::new(rhs)),
+                    GradientOperation::Divide => Expression::Divide(Box::new(lhs), Box::new(rhs)),
+                    GradientOperation::Power => Expression::Power(Box::new(lhs), Box::new(rhs)),
+                    GradientOperation::ComposeX => rhs.substitute(Variable::X, &lhs),
+                    GradientOperation::ComposeY => rhs.substitute(Variable::Y, &lhs),
+                }
+            },
+            FieldNode::Group(inner) | FieldNode::OpenGroup(inner) => inner.to_expression(),
+        }
+    }
+
+    /// Combine a new function with this tree. The function takes as much of the tree as the operation's precedence allows,
+    /// so adding "* c" to "a + b" gives "a + b * c" rather than "(a + b) * c"
+    fn insert(self, operation: GradientOperation, node: FieldNode) -> FieldNode {
+        match self {
+            FieldNode::OpenGroup(inner) => FieldNode::OpenGroup(Box::new(inner.insert(operation, node))), // stays inside the open group
+            FieldNode::Operation { operation: existing, lhs, rhs } if rhs.has_open_group() || (!existing.is_composition()
+                && (existing.precedence() < operation.precedence() || (existing == operation && operation.right_associative()))) => {
+                // new operation binds tighter (or an open group is on the right), so it only applies to the right side of the existing operation
+                FieldNode::Operation { operation: existing, lhs, rhs: Box::new(rhs.insert(operation, node)) }
+            },
+            other => FieldNode::Operation { operation, lhs: Box::new(other), rhs: Box::new(node) },
+        }
+    }
+
+    /// Remove the function with the given id along with the operation attaching it. Returns None if nothing is left
+    fn remove(self, function_id: u32) -> Option<FieldNode> {
+        match self {
+            FieldNode::Function { id, .. } if id == function_id => None,
+            FieldNode::Function { .. } => Some(self),
+            FieldNode::Operation { operation, lhs, rhs } => match (lhs.remove(function_id), rhs.remove(function_id)) {
+                (Some(lhs), Some(rhs)) => Some(FieldNode::Operation { operation, lhs: Box::new(lhs), rhs: Box::new(rhs) }),
+                (Some(remaining), None) | (None, Some(remaining)) => Some(remaining), // operation goes away with the function
+                (None, None) => None,
+            },
+            FieldNode::Group(inner) => inner.remove(function_id).map(|inner| FieldNode::Group(Box::new(inner))),
+            FieldNode::OpenGroup(inner) => inner.remove(function_id).map(|inner| FieldNode::OpenGroup(Box::new(inner))),
+        }
+    }
+
+    /// Whether a group is open at the end of this part of the tree. Open groups are only ever on the right, where
+    /// functions are added
+    fn has_open_group(&self) -> bool {
+        match self {
+            FieldNode::OpenGroup(_) => true,
+            FieldNode::Operation { rhs, .. } => rhs.has_open_group(),
+            FieldNode::Function { .. } | FieldNode::Group(_) => false,
+        }
+    }
+
+    /// Open a group starting at the last function added, so that the functions added next are grouped with it
+    fn open_group(self) -> FieldNode {
+        match self {
+            FieldNode::Operation { operation, lhs, rhs } => FieldNode::Operation { operation, lhs, rhs: Box::new(rhs.open_group()) },
+            FieldNode::OpenGroup(inner) => FieldNode::OpenGroup(Box::new(inner.open_group())), // groups can be nested
+            other => FieldNode::OpenGroup(Box::new(other)),
+        }
+    }
+
+    /// Close the innermost open group. Does nothing if no group is open
+    fn close_group(self) -> FieldNode {
+        match self {
+            FieldNode::Operation { operation, lhs, rhs } => FieldNode::Operation { operation, lhs, rhs: Box::new(rhs.close_group()) },
+            FieldNode::OpenGroup(inner) if inner.has_open_group() => FieldNode::OpenGroup(Box::new(inner.close_group())),
+            FieldNode::OpenGroup(inner) => FieldNode::Group(inner),
+            other => other,
+        }
+    }
+
+    /// Binding strength of this part of the tree when displayed
+    fn precedence(&self) -> u8 {
+        match self {
+            FieldNode::Function { function, .. } => function.precedence(),
+            FieldNode::Operation { operation, .. } if operation.is_composition() => self.to_expression().precedence(), // shown as the substituted function
+            FieldNode::Operation { operation, .. } => operation.precedence(),
+            FieldNode::Group(_) | FieldNode::OpenGroup(_) => u8::MAX, // always in parentheses already
+        }
+    }
+
+    /// Write part of the tree, wrapped in parentheses if it binds less tightly than required. Right hand sides
+    /// starting with a minus sign are also wrapped, so "x * -1" shows as "x * (-1)"
+    fn write_operand(&self, f: &mut fmt::Formatter, minimum_precedence: u8, right_hand_side: bool) -> fmt::Result {
+        if self.precedence() < minimum_precedence || (right_hand_side && self.to_string().starts_with('-')) {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl fmt::Display for FieldNode {
+    /// Text of the tree exactly as it is evaluated. Groups always show their parentheses
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldNode::Function { function, .. } => write!(f, "{}", function),
+            FieldNode::Operation { operation, .. } if operation.is_composition() => write!(f, "{}", self.to_expression()),
+            FieldNode::Operation { operation, lhs, rhs } => {
+                let symbol = match operation {
+                    GradientOperation::Add => "+",
+                    GradientOperation::Subtract => "-",
+                    GradientOperation::Multiply => "*",
+                    GradientOperation::Divide => "/",
+                    GradientOperation::Power => "^",
+                    GradientOperation::ComposeX | GradientOperation::ComposeY => unreachable!(), // handled above
+                };
+
+                // the side that is grouped first doesn't need parentheses for the same operation
+                let (lhs_precedence, rhs_precedence) = match operation.right_associative() {
+                    true => (operation.precedence() + 1, operation.precedence()),
+                    false => (operation.precedence(), operation.precedence() + 1),
+                };
+
+                lhs.write_operand(f, lhs_precedence, false)?;
+                write!(f, " {} ", symbol)?;
+                rhs.write_operand(f, rhs_precedence, true)
+            },
+            FieldNode::Group(inner) => write!(f, "({})", inner),
+            FieldNode::OpenGroup(inner) => write!(f, "({}", inner), // closing parenthesis comes when the group is closed
+        }
     }
 }
 
 /// This component stores the gradient field of a given level. X and Y velocities at a point.
 #[derive(Component)]
 pub struct Gradient {
-    pub x_functions: Vec<(u32, GradientOperation, fn(f32, f32) -> f32, String)>, // (function id (for current level), operation to combine with previous functions, function itself, string representing function)
-    pub y_functions: Vec<(u32, GradientOperation, fn(f32, f32) -> f32, String)>, // (function id (for current level), operation to combine with previous functions, function itself, string representing function)
+    pub x_component: Option<FieldNode>, // tree of functions making up the x component, None if there are no functions
+    pub y_component: Option<FieldNode>, // tree of functions making up the y component, None if there are no functions
+    pub potential: Option<Expression>, // potential f the field is the gradient of, if the player built the field from one
 }   
 
 impl Gradient {
-    /// Compute the x value of the current gradient function at the specified point 
+    /// Compute the x value of the current gradient function at the specified point. Points where the function
+    /// isn't defined (such as sqrt(x) for negative x) evaluate to 0
     pub fn x(&self, x: f32, y: f32) -> f32 {
-        if self.x_functions.len() == 0 { // if no x functions currently in gradient, evaulate to 0
-            return 0.
-        } else {
-            let mut x_value = self.x_functions[0].2(x, y); // evaulate the first x function 
-            for function in self.x_functions.iter().skip(1) { // iterate through all other x functions
-                match function.1 { // match operation to combine with previous functions
-                    GradientOperation::Add => x_value = x_value + function.2(x,y), // add function to previous function
-                    GradientOperation::Multiply => x_value = x_value * function.2(x,y), // multiply function to previous function
-                }
-            }
-
-            FIELD_SCALE*x_value // return computed value 
+        match &self.x_component {
+            Some(x_component) => finite_or_zero(FIELD_SCALE*x_component.evaluate(x, y)), // return computed value 
+            None => 0., // if no x functions currently in gradient, evaulate to 0
         }
     }
 
-    /// Compute the y value of the current gradient function at the specified point 
+    /// Compute the y value of the current gradient function at the specified point. Points where the function
+    /// isn't defined (such as sqrt(y) for negative y) evaluate to 0
     pub fn y(&self, x: f32, y: f32) -> f32 {
-        if self.y_functions.len() == 0 { // if no y functions currently in gradient, evaulate to 0 
-            return 0.
-        } else {
-            let mut y_value = self.y_functions[0].2(x, y); // evaulate the first y function
-            for function in self.y_functions.iter().skip(1) { // iterate through all other y functions
-                match function.1 { // match operation to combine with previous functions
-                    GradientOperation::Add => y_value = y_value + function.2(x,y), // add function to previous function
-                    GradientOperation::Multiply => y_value = y_value * function.2(x,y), // multiply function to previous function
-                }
-            }
-
-            FIELD_SCALE*y_value // return computed value
+        match &self.y_component {
+            Some(y_component) => finite_or_zero(FIELD_SCALE*y_component.evaluate(x, y)), // return computed value 
+            None => 0., // if no y functions currently in gradient, evaulate to 0
         }
     }
 
     /// Add a new x function to the gradient 
-    pub fn add_x_function(&mut self, function_id: u32, operation: GradientOperation, function: fn(f32, f32) -> f32, function_string: String) {
-        self.x_functions.push((function_id, operation, function, function_string)); // add function to x functions
+    pub fn add_x_function(&mut self, function_id: u32, operation: GradientOperation, function: Expression) {
+        self.potential = None; // field no longer comes from the potential
+        self.x_component = Some(add_function(self.x_component.take(), function_id, operation, function));
     }
 
     /// Add a new y function to the gradient
-    pub fn add_y_function(&mut self, function_id: u32, operation: GradientOperation, function: fn(f32, f32) -> f32, function_string: String) {
-        self.y_functions.push((function_id, operation, function, function_string)); // add function to y functions
+    pub fn add_y_function(&mut self, function_id: u32, operation: GradientOperation, function: Expression) {
+        self.potential = None; // field no longer comes from the potential
+        self.y_component = Some(add_function(self.y_component.take(), function_id, operation, function));
+    }
+
+    /// Replace all x functions with a single function, as when the player types the x component in directly
+    pub fn set_x_function(&mut self, function: Expression) {
+        self.potential = None; // field no longer comes from the potential
+        self.x_component = Some(FieldNode::Function { id: 0, function });
+    }
+
+    /// Replace all y functions with a single function, as when the player types the y component in directly
+    pub fn set_y_function(&mut self, function: Expression) {
+        self.potential = None; // field no longer comes from the potential
+        self.y_component = Some(FieldNode::Function { id: 0, function });
+    }
+
+    /// Make the field the gradient of a potential f, so the x and y components are the partial derivatives of f
+    pub fn set_potential(&mut self, potential: Expression) {
+        self.x_component = Some(FieldNode::Function { id: 0, function: potential.derivative(Variable::X) }); // df/dx
+        self.y_component = Some(FieldNode::Function { id: 0, function: potential.derivative(Variable::Y) }); // df/dy
+        self.potential = Some(potential);
     }
 
     /// Remove x function from gradient. If function_id is not found, do nothing 
     pub fn remove_x_function(&mut self, function_id: u32) {
-        let function_pos = &self.x_functions.iter().position(|x| x.0 == function_id); // search for location of function id
-
-        match function_pos {
-            Some(pos) => {self.x_functions.remove(*pos);}, // if function found, remove it from vector
-            None => (),
-        }
+        self.potential = None; // field no longer comes from the potential
+        self.x_component = self.x_component.take().and_then(|x_component| x_component.remove(function_id));
     }
 
     /// Remove y function from gradient. If function_id is not found, do nothing
     pub fn remove_y_function(&mut self, function_id: u32) {
-        let function_pos = &self.y_functions.iter().position(|x| x.0 == function_id); // search for location of function id
+        self.potential = None; // field no longer comes from the potential
+        self.y_component = self.y_component.take().and_then(|y_component| y_component.remove(function_id));
+    }
+
+    /// Group everything in the x component so far, so that later functions apply to all of it 
+    pub fn group_x_functions(&mut self) {
+        self.x_component = self.x_component.take().map(group);
+    }
+
+    /// Group everything in the y component so far, so that later functions apply to all of it 
+    pub fn group_y_functions(&mut self) {
+        self.y_component = self.y_component.take().map(group);
+    }
+
+    /// Open a group in the x component at its last function, so that the functions added next are grouped with it
+    pub fn open_x_group(&mut self) {
+        self.x_component = self.x_component.take().map(FieldNode::open_group);
+    }
+
+    /// Open a group in the y component at its last function, so that the functions added next are grouped with it
+    pub fn open_y_group(&mut self) {
+        self.y_component = self.y_component.take().map(FieldNode::open_group);
+    }
+
+    /// Close the innermost open group in the x component
+    pub fn close_x_group(&mut self) {
+        self.x_component = self.x_component.take().map(FieldNode::close_group);
+    }
+
+    /// Close the innermost open group in the y component
+    pub fn close_y_group(&mut self) {
+        self.y_component = self.y_component.take().map(FieldNode::close_group);
+    }
 
-        match function_pos {
-            Some(pos) => {self.y_functions.remove(*pos);}, // if function found, remove it from vector
-            None => (),
+    /// The x component as a single expression, without FIELD_SCALE. 0 if there are no x functions
+    pub fn x_expression(&self) -> Expression {
+        match &self.x_component {
+            Some(x_component) => x_component.to_expression(),
+            None => Expression::Number(0.),
+        }
+    }
+
+    /// The y component as a single expression, without FIELD_SCALE. 0 if there are no y functions
+    pub fn y_expression(&self) -> Expression {
+        match &self.y_component {
+            Some(y_component) => y_component.to_expression(),
+            None => Expression::Number(0.),
         }
     }
 
     /// Generates text representing the current gradient function for the x direction 
     pub fn x_text(&self) -> String {
-        if self.x_functions.len() == 0 { // if no x functions currently in gradient, return 0
-            return String::from("0")
-        } else {
-            let mut x_text = self.x_functions[0].3.clone(); // get string representing first x function 
-            for function in self.x_functions.iter().skip(1) { // iterate through all other x functions 
-                match function.1 { // how functions are combined depends on the operation 
-                    GradientOperation::Add => {x_text = format!("{} + {}", x_text, function.3);}, // add function to previous function
-                    GradientOperation::Multiply => {x_text = format!("({}) * {}", x_text, function.3)}, // with multiplication, need to add parenthesis 
-                }
-            }
-            x_text // return string representing gradient function
+        match &self.x_component {
+            Some(x_component) => x_component.to_string(), // string representing gradient function
+            None => String::from("0"), // if no x functions currently in gradient, return 0
         }
     }
 
     /// Generates text representing the current gradient function for the y direction 
     pub fn y_text(&self) -> String {
-        if self.y_functions.len() == 0 { // if no y functions currently in gradient, return 0
-            return String::from("0")
-        } else {
-            let mut y_text = self.y_functions[0].3.clone(); // get string representing first y function 
-            for function in self.y_functions.iter().skip(1) { // iterate through all other y functions 
-                match function.1 { // how functions are combined depends on the operation 
-                    GradientOperation::Add => {y_text = format!("{} + {}", y_text, function.3);}, // add function to previous function
-                    GradientOperation::Multiply => {y_text = format!("({}) * {}", y_text, function.3)}, // with multiplication, need to add parenthesis 
-                }
-            }
-            y_text // return string representing gradient function
+        match &self.y_component {
+            Some(y_component) => y_component.to_string(), // string representing gradient function
+            None => String::from("0"), // if no y functions currently in gradient, return 0
+        }
+    }
+
+    /// Each function in the x direction with its value at a point, before scaling by FIELD_SCALE
+    pub fn x_terms(&self, x: f32, y: f32) -> Vec<(String, f32)> {
+        match &self.x_component {
+            Some(x_component) => x_component.terms(x, y),
+            None => Vec::new(), // no x functions to show
         }
     }
 
+    /// Each function in the y direction with its value at a point, before scaling by FIELD_SCALE
+    pub fn y_terms(&self, x: f32, y: f32) -> Vec<(String, f32)> {
+        match &self.y_component {
+            Some(y_component) => y_component.terms(x, y),
+            None => Vec::new(), // no y functions to show
+        }
+    }
+
+    /// Number of functions used across both components. A typed component counts as one function
+    pub fn function_count(&self) -> u32 {
+        [&self.x_component, &self.y_component].iter()
+            .filter_map(|component| component.as_ref())
+            .map(FieldNode::function_count)
+            .sum()
+    }
+
+    /// Number of operations used across both components
+    pub fn operation_count(&self) -> u32 {
+        [&self.x_component, &self.y_component].iter()
+            .filter_map(|component| component.as_ref())
+            .map(FieldNode::operation_count)
+            .sum()
+    }
+
     /// Clears gradient field back to 0
     pub fn clear_field(&mut self) {
-        self.x_functions.clear(); // clear x functions
-        self.y_functions.clear(); // clear y functions
+        self.x_component = None; // clear x functions
+        self.y_component = None; // clear y functions
+        self.potential = None; // clear potential
     }
 
     /// Get magnitude of the gradient at a point 
@@ -138,17 +491,102 @@ impl Gradient {
     pub fn new() -> Self {
         // empty grad 
         Gradient {
-            x_functions: Vec::new(),
-            y_functions: Vec::new(),
+            x_component: None,
+            y_component: None,
+            potential: None,
         }
     }
 }
 
+/// Add a function to one component of the gradient. The first function in a component ignores the operation
+pub fn add_function(component: Option<FieldNode>, function_id: u32, operation: GradientOperation, function: Expression) -> FieldNode {
+    let node = FieldNode::Function { id: function_id, function };
+
+    match component {
+        Some(component) => component.insert(operation, node),
+        None => node,
+    }
+}
+
+/// Replace NaN and infinite values with 0, so the player never flies off to infinity
+fn finite_or_zero(value: f32) -> f32 {
+    if value.is_finite() {
+        value
+    } else {
+        0.
+    }
+}
+
+/// Wrap a component in a group, closing any groups still open inside it. A component that is already a group is
+/// left alone
+pub fn group(mut component: FieldNode) -> FieldNode {
+    while component.has_open_group() {
+        component = component.close_group();
+    }
+
+    match component {
+        FieldNode::Group(_) => component,
+        other => FieldNode::Group(Box::new(other)),
+    }
+}
+
 /// Function for initializing gradient object
 fn initialize_gradient(mut commands: Commands) {
     commands.spawn(Gradient::new());
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// How the size of an arrow follows the field's magnitude
+pub enum ArrowScaling {
+    Linear, // size proportional to magnitude
+    Log, // size grows with the log of magnitude, so weak parts of the field still show
+    Root, // size grows with a root of magnitude, strongly compressing large magnitudes
+    Uniform, // every arrow is the same size, only the color shows magnitude
+}
+
+impl ArrowScaling {
+    /// Scaling the arrow scaling key switches to next
+    pub fn next(&self) -> Self {
+        match self {
+            ArrowScaling::Linear => ArrowScaling::Log,
+            ArrowScaling::Log => ArrowScaling::Root,
+            ArrowScaling::Root => ArrowScaling::Uniform,
+            ArrowScaling::Uniform => ArrowScaling::Linear,
+        }
+    }
+
+    /// Size of an arrow relative to the largest, for an arrow with the given fraction of the largest magnitude
+    pub fn relative_size(&self, fraction: f32) -> f32 {
+        let fraction = fraction.clamp(0., 1.);
+
+        match self {
+            ArrowScaling::Linear => fraction,
+            ArrowScaling::Log => (1. + ARROW_LOG_SHARPNESS * fraction).ln() / (1. + ARROW_LOG_SHARPNESS).ln(),
+            ArrowScaling::Root => fraction.powf(1. / ARROW_ROOT),
+            ArrowScaling::Uniform => if fraction > 0. { 1. } else { 0. }, // still hide arrows where there is no field
+        }
+    }
+}
+
+#[derive(Resource)]
+/// Settings for the grid of gradient arrows. The grid is respawned whenever these change
+pub struct ArrowSettings {
+    pub arrows_x: u32, // number of arrows in x direction
+    pub arrows_y: u32, // number of arrows in y direction
+    pub scaling: ArrowScaling, // how arrow size follows the field's magnitude
+}
+
+impl ArrowSettings {
+    /// New method. Starts with the default grid and root scaling
+    pub fn new() -> Self {
+        Self {
+            arrows_x: NUM_ARROWS_X,
+            arrows_y: NUM_ARROWS_Y,
+            scaling: ArrowScaling::Root,
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct GradientArrow {
     pub x: f32, // x coordinate 
@@ -159,14 +597,30 @@ pub struct GradientArrow {
     pub angle: f32, // angle of arrow corresponding to the gradient at the point (x, y)
 }
 
-fn spawn_gradient_arrows(mut commands: Commands, asset_server: Res<AssetServer>) {
-    for i in 0..NUM_ARROWS_X {
-        for j in 0..NUM_ARROWS_Y {
+/// Spawn the grid of arrows, replacing the old grid, whenever the arrow settings change
+fn spawn_gradient_arrows(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<ArrowSettings>,
+    render_mode: Res<State<FieldRenderMode>>,
+    old_arrows: Query<Entity, With<GradientArrow>>,
+) {
+    if !settings.is_changed() { // also changed when first added, which spawns the first grid
+        return
+    }
+
+    for entity in old_arrows.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    for i in 0..settings.arrows_x {
+        for j in 0..settings.arrows_y {
             commands.spawn(SpriteBundle {
                 texture: asset_server.load("../assets/arrow.png"),
                 transform: Transform::from_xyz(0., 0., 0.) // set initial position to (0,0)
                     .with_scale(Vec3::new(BASE_ARROW_SCALE, BASE_ARROW_SCALE, 1.)) // with no scaling 
                     .with_rotation(Quat::from_rotation_z(0.)), // with no rotation
+                visibility: Visibility { is_visible: *render_mode.current() == FieldRenderMode::Arrows }, // arrows are hidden in other render modes
                 ..default()
             })
             .insert(GradientArrow {
@@ -181,17 +635,43 @@ fn spawn_gradient_arrows(mut commands: Commands, asset_server: Res<AssetServer>)
     }
 }
 
+/// Change the arrow settings from the keyboard: [ and ] for fewer or more arrows, backslash to switch the scaling.
+/// Ignored while typing in an expression input
+fn arrow_settings_keyboard_system(
+    keys: Res<Input<KeyCode>>,
+    expression_inputs: Query<&ExpressionInput>,
+    mut settings: ResMut<ArrowSettings>,
+) {
+    if expression_inputs.iter().any(|expression_input| expression_input.focused) {
+        return
+    }
+
+    if keys.just_pressed(KeyCode::LBracket) && settings.arrows_y > MIN_ARROWS { // fewer arrows
+        settings.arrows_x = (settings.arrows_x - ARROW_DENSITY_STEP).max(MIN_ARROWS);
+        settings.arrows_y = (settings.arrows_y - ARROW_DENSITY_STEP).max(MIN_ARROWS);
+    }
+
+    if keys.just_pressed(KeyCode::RBracket) && settings.arrows_y < MAX_ARROWS { // more arrows
+        settings.arrows_x = (settings.arrows_x + ARROW_DENSITY_STEP).min(MAX_ARROWS);
+        settings.arrows_y = (settings.arrows_y + ARROW_DENSITY_STEP).min(MAX_ARROWS);
+    }
+
+    if keys.just_pressed(KeyCode::Backslash) {
+        settings.scaling = settings.scaling.next();
+    }
+}
+
 fn update_gradient_arrows(
     mut gradient_arrows: Query<(&mut GradientArrow, &mut Sprite, &mut Transform)>,
     gradient: Query<&Gradient>,
-    wnds: Res<Windows>,
+    rect: Res<VisibleRect>,
+    render_mode: Res<State<FieldRenderMode>>,
+    settings: Res<ArrowSettings>,
+    range: Res<MagnitudeRange>,
 ) {
-    // get main window (from https://bevy-cheatbook.github.io/cookbook/cursor2world.html)
-    let wnd = wnds.get_primary().unwrap();
-    
-    let pixel_to_coord_scale = wnd.height() / VERTICAL_WINDOW_HEIGHT; // scale factor to convert from world coordinates to pixels
-
-    let window_width = wnd.width() / pixel_to_coord_scale; // width of the window in world coordinates
+    if *render_mode.current() != FieldRenderMode::Arrows { // arrows are hidden in other render modes
+        return
+    }
 
     let gradient = gradient.single(); // should only be 1 gradient
 
@@ -201,8 +681,8 @@ fn update_gradient_arrows(
     for (mut gradient_arrow, _sprite, _transform) in gradient_arrows.iter_mut() {
         let (x_number, y_number) = (gradient_arrow.x_number, gradient_arrow.y_number); // get the index of the arrow in the x and y directions
 
-        gradient_arrow.x = (x_number as f32) * window_width/((NUM_ARROWS_X as f32)-1.) - window_width/2.; // get the x coordinate of the arrow
-        gradient_arrow.y = (y_number as f32) * VERTICAL_WINDOW_HEIGHT/((NUM_ARROWS_Y as f32)-1.) - VERTICAL_WINDOW_HEIGHT/2.; // get the y coordinate of the arrow 
+        gradient_arrow.x = rect.min_x + (x_number as f32) * rect.width()/((settings.arrows_x as f32)-1.); // get the x coordinate of the arrow
+        gradient_arrow.y = rect.min_y + (y_number as f32) * rect.height()/((settings.arrows_y as f32)-1.); // get the y coordinate of the arrow 
 
         gradient_arrow.scale = BASE_ARROW_SCALE * gradient.magnitude(gradient_arrow.x, gradient_arrow.y); // get the scaling factor for the arrow
 
@@ -211,12 +691,14 @@ fn update_gradient_arrows(
         }
     }
 
+    let spacing_scale = (NUM_ARROWS_Y as f32) / (settings.arrows_y as f32) * rect.height() / VERTICAL_WINDOW_HEIGHT; // denser grids and zooming in give smaller arrows so they don't overlap
+
     for (mut gradient_arrow, mut sprite, mut transform) in gradient_arrows.iter_mut() {
-        let scale = (EXPECTED_MAX_ARROW_SCALE*BASE_ARROW_SCALE)*(gradient_arrow.scale/(0.6*max_magnitude)).powf(1./7.);
+        let scale = (EXPECTED_MAX_ARROW_SCALE*BASE_ARROW_SCALE) * spacing_scale * settings.scaling.relative_size(gradient_arrow.scale/max_magnitude);
 
         gradient_arrow.angle = gradient.y(gradient_arrow.x, gradient_arrow.y).atan2(gradient.x(gradient_arrow.x, gradient_arrow.y)) - 0.25*PI; // get the angle of the arrow
 
-        sprite.color = Color::hsla(240. - gradient_arrow.scale/(max_magnitude)*240., 1., 0.8, 1.);
+        sprite.color = range.color(gradient_arrow.scale/BASE_ARROW_SCALE, 0.8); // same colors as the legend
 
         *transform = Transform::from_xyz(gradient_arrow.x, gradient_arrow.y, 0.) // set position to (x,y)
             .with_scale(Vec3::new(scale, scale, gradient_arrow.scale)) // edit scaling
@@ -232,7 +714,40 @@ pub struct GradientArrowPlugin; // plugin for spawning and controlling gradient
 /// special state for gradient operation 
 pub enum GradientOperationState {
     Add, // add operation 
+    Subtract, // subtract operation 
     Multiply, // multiply operation 
+    Divide, // divide operation 
+    Power, // power operation 
+    ComposeX, // compose into x operation 
+    ComposeY, // compose into y operation 
+}
+
+impl GradientOperationState {
+    /// Gradient operation for this state 
+    pub fn operation(&self) -> GradientOperation {
+        match self {
+            GradientOperationState::Add => GradientOperation::Add,
+            GradientOperationState::Subtract => GradientOperation::Subtract,
+            GradientOperationState::Multiply => GradientOperation::Multiply,
+            GradientOperationState::Divide => GradientOperation::Divide,
+            GradientOperationState::Power => GradientOperation::Power,
+            GradientOperationState::ComposeX => GradientOperation::ComposeX,
+            GradientOperationState::ComposeY => GradientOperation::ComposeY,
+        }
+    }
+
+    /// State for a gradient operation 
+    pub fn from_operation(operation: GradientOperation) -> Self {
+        match operation {
+            GradientOperation::Add => GradientOperationState::Add,
+            GradientOperation::Subtract => GradientOperationState::Subtract,
+            GradientOperation::Multiply => GradientOperationState::Multiply,
+            GradientOperation::Divide => GradientOperationState::Divide,
+            GradientOperation::Power => GradientOperationState::Power,
+            GradientOperation::ComposeX => GradientOperationState::ComposeX,
+            GradientOperation::ComposeY => GradientOperationState::ComposeY,
+        }
+    }
 }
 
 /// Plugin implementation 
@@ -240,8 +755,79 @@ impl Plugin for GradientArrowPlugin {
     /// Initialization 
     fn build(&self, app: &mut App) {
         app.add_startup_system(initialize_gradient);
-        app.add_startup_system(spawn_gradient_arrows);
+        app.insert_resource(ArrowSettings::new());
+        app.add_system(spawn_gradient_arrows);
+        app.add_system(arrow_settings_keyboard_system.before(spawn_gradient_arrows));
         app.add_system(update_gradient_arrows);
         app.add_state(GradientOperationState::Add); // state for when adding new functions to gradient... operation to add with 
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Function node for a button, from the text of its function
+    fn function(id: u32, text: &str) -> FieldNode {
+        FieldNode::Function { id, function: Expression::parse(text).unwrap() }
+    }
+
+    #[test]
+    fn displayed_text_parses_back_to_the_same_values() {
+        let functions = ["x^2", "-x", "y", "2", "x - y", "sin(x)", "x/y", "-1", "e^x", "2x"];
+        let points = [(0.5, 1.5), (1.3, -0.7), (-2.1, 0.4), (2.7, 3.1)];
+
+        for (a, b, c) in [(0, 2, 1), (1, 0, 3), (4, 6, 0), (9, 1, 7), (5, 8, 2), (3, 0, 4)] {
+            for first in GradientOperation::ALL {
+                for second in GradientOperation::ALL {
+                    let component = add_function(None, 0, first, Expression::parse(functions[a]).unwrap());
+                    let component = add_function(Some(component), 1, first, Expression::parse(functions[b]).unwrap());
+                    let component = add_function(Some(component), 2, second, Expression::parse(functions[c]).unwrap());
+                    let text = component.to_string();
+                    let reparsed = Expression::parse(&text).unwrap();
+
+                    for (x, y) in points {
+                        let (value, reparsed_value) = (component.evaluate(x, y), reparsed.evaluate(x, y));
+                        assert!(
+                            (value.is_nan() && reparsed_value.is_nan()) || (value - reparsed_value).abs() <= 1e-3 * (1. + value.abs()),
+                            "{:?} shows as \"{}\", which gives {} instead of {} at ({}, {})", component, text, reparsed_value, value, x, y,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn power_of_a_power_function_shows_its_base_in_parentheses() {
+        let component = function(0, "x^2").insert(GradientOperation::Power, function(1, "y"));
+        assert_eq!(component.to_string(), "(x^2) ^ y");
+    }
+
+    #[test]
+    fn open_and_closed_groups_hold_the_functions_added_inside_them() {
+        // a * (b + c) * d, built by opening a group at b and closing it after c
+        let component = function(0, "x").insert(GradientOperation::Multiply, function(1, "y"));
+        let component = component.open_group().insert(GradientOperation::Add, function(2, "2"));
+        assert_eq!(component.to_string(), "x * (y + 2");
+
+        let component = component.close_group().insert(GradientOperation::Multiply, function(3, "3"));
+        assert_eq!(component.to_string(), "x * (y + 2) * 3");
+        assert_eq!(component.evaluate(2., 1.), 2.*(1. + 2.)*3.);
+    }
+
+    #[test]
+    fn grouping_everything_closes_open_groups() {
+        let component = function(0, "x").open_group().insert(GradientOperation::Add, function(1, "y"));
+        let component = group(component).insert(GradientOperation::Multiply, function(2, "2"));
+        assert_eq!(component.to_string(), "(x + y) * 2"); // already a group once closed
+        assert!(!component.has_open_group());
+    }
+
+    #[test]
+    fn removing_a_function_keeps_its_group_open() {
+        let component = function(0, "x").insert(GradientOperation::Add, function(1, "y")).open_group()
+            .insert(GradientOperation::Multiply, function(2, "2"));
+        let component = component.remove(2).unwrap().insert(GradientOperation::Subtract, function(3, "3"));
+        assert_eq!(component.to_string(), "x + (y - 3");
+    }
+}
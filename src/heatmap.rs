@@ -0,0 +1,260 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::Gradient;
+use crate::camera::VisibleRect;
+use crate::field_render::FieldRenderMode;
+use crate::ui::ExpressionInput;
+
+use crate::constants::{
+    FIELD_SCALE,
+    HEATMAP_HEIGHT,
+    HEATMAP_LIGHTNESS,
+    HEATMAP_ALPHA,
+    LEGEND_SEGMENTS,
+    LEGEND_WIDTH,
+    LEGEND_HEIGHT,
+    ANALYSIS_TEXT_COLOR,
+    BUTTON_SPACING,
+};
+
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+/// Smallest and largest magnitude of the field over the visible window. Every way of drawing the field colors by
+/// where a magnitude falls in this range, from blue at the smallest to red at the largest, so one legend explains them all
+pub struct MagnitudeRange {
+    pub min: f32, // smallest magnitude, from Gradient::magnitude
+    pub max: f32, // largest magnitude, from Gradient::magnitude
+}
+
+impl MagnitudeRange {
+    /// New method. The empty field is 0 everywhere
+    pub fn new() -> Self {
+        Self {
+            min: 0.,
+            max: 0.,
+        }
+    }
+
+    /// Where a magnitude falls in the range, from 0 at the smallest to 1 at the largest
+    pub fn fraction(&self, magnitude: f32) -> f32 {
+        if self.max - self.min > f32::EPSILON {
+            ((magnitude - self.min) / (self.max - self.min)).clamp(0., 1.)
+        } else { // field is the same strength everywhere
+            0.
+        }
+    }
+
+    /// Color for a magnitude, from blue at the smallest to red at the largest
+    pub fn color(&self, magnitude: f32, lightness: f32) -> Color {
+        fraction_color(self.fraction(magnitude), lightness)
+    }
+}
+
+/// Color for a fraction of the way through the magnitude range, from blue at 0 to red at 1
+pub fn fraction_color(fraction: f32, lightness: f32) -> Color {
+    Color::hsla(240. - fraction * 240., 1., lightness, 1.)
+}
+
+#[derive(Resource)]
+/// Whether the heatmap is shown behind the field
+pub struct HeatmapSettings {
+    pub visible: bool, // toggled with H
+}
+
+#[derive(Component)]
+/// Struct to label the sprite showing the heatmap
+pub struct Heatmap;
+
+#[derive(Component)]
+/// Struct to label the text at either end of the legend
+pub struct LegendText {
+    pub max: bool, // whether the text is for the largest magnitude (at the top) or the smallest (at the bottom)
+}
+
+/// Magnitudes of the field at the center of each pixel of a width x height image covering the visible rectangle,
+/// with the first row at the top
+pub fn sample_magnitudes(gradient: &Gradient, rect: &VisibleRect, width: u32, height: u32) -> Vec<f32> {
+    let pixel_size = rect.height() / height as f32; // world units per pixel
+
+    let mut magnitudes = Vec::with_capacity((width * height) as usize);
+    for row in 0..height {
+        for column in 0..width {
+            let x = rect.min_x + (column as f32 + 0.5) * pixel_size;
+            let y = rect.max_y - (row as f32 + 0.5) * pixel_size;
+
+            magnitudes.push(gradient.magnitude(x, y));
+        }
+    }
+
+    magnitudes
+}
+
+/// Range of a set of sampled magnitudes
+pub fn magnitude_range(magnitudes: &[f32]) -> MagnitudeRange {
+    if magnitudes.is_empty() {
+        return MagnitudeRange::new()
+    }
+
+    MagnitudeRange {
+        min: magnitudes.iter().cloned().fold(f32::INFINITY, f32::min),
+        max: magnitudes.iter().cloned().fold(f32::NEG_INFINITY, f32::max),
+    }
+}
+
+/// Sample the field's magnitude over the view whenever the field changes or the camera moves. Updates the
+/// magnitude range and redraws the heatmap
+pub fn heatmap_update(
+    gradient: Query<(&Gradient, ChangeTrackers<Gradient>)>,
+    rect: Res<VisibleRect>,
+    mut range: ResMut<MagnitudeRange>,
+    mut images: ResMut<Assets<Image>>,
+    mut heatmap: Query<(&Handle<Image>, &mut Sprite, &mut Transform), With<Heatmap>>,
+) {
+    let (gradient, gradient_tracker) = gradient.single(); // should only be 1 gradient
+
+    if !gradient_tracker.is_changed() && !rect.is_changed() {
+        return
+    }
+
+    let height = HEATMAP_HEIGHT;
+    let width = ((HEATMAP_HEIGHT as f32) * rect.width() / rect.height()).round().max(1.) as u32; // square pixels
+
+    let magnitudes = sample_magnitudes(gradient, &rect, width, height);
+    let new_range = magnitude_range(&magnitudes);
+
+    if *range != new_range { // only mark as changed when it actually changes
+        *range = new_range;
+    }
+
+    let (handle, mut sprite, mut transform) = heatmap.single_mut();
+
+    let mut data = Vec::with_capacity(magnitudes.len() * 4);
+    for magnitude in magnitudes {
+        let mut color = new_range.color(magnitude, HEATMAP_LIGHTNESS);
+        color.set_a(HEATMAP_ALPHA);
+
+        data.extend(color.as_rgba_f32().iter().map(|channel| (channel * 255.) as u8));
+    }
+
+    if let Some(image) = images.get_mut(handle) {
+        *image = Image::new(
+            Extent3d { width, height, depth_or_array_layers: 1 },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        );
+    }
+
+    sprite.custom_size = Some(Vec2::new(rect.width(), rect.height())); // cover the view
+    (transform.translation.x, transform.translation.y) = rect.center();
+}
+
+/// Spawn the heatmap sprite and the legend on the right of the window
+fn heatmap_setup(mut commands: Commands, mut images: ResMut<Assets<Image>>, asset_server: Res<AssetServer>) {
+    let texture = images.add(Image::new_fill(
+        Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+    )); // filled in on the first frame
+
+    commands
+        .spawn(SpriteBundle {
+            texture,
+            transform: Transform::from_xyz(0., 0., -0.06), // below everything else
+            ..default()
+        })
+        .insert(Heatmap);
+
+    let style = TextStyle {
+        font: asset_server.load("fonts/tahoma.ttf"),
+        font_size: 16.0,
+        color: ANALYSIS_TEXT_COLOR,
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(BUTTON_SPACING),
+                    top: Val::Percent(30.),
+                    ..default()
+                },
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("|gradient|", style.clone()));
+            parent
+                .spawn(TextBundle::from_section("", style.clone()))
+                .insert(LegendText { max: true });
+
+            for segment in 0..LEGEND_SEGMENTS { // strongest at the top
+                let fraction = 1. - segment as f32 / (LEGEND_SEGMENTS as f32 - 1.);
+
+                parent.spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(LEGEND_WIDTH), Val::Px(LEGEND_HEIGHT / LEGEND_SEGMENTS as f32)),
+                        ..default()
+                    },
+                    background_color: fraction_color(fraction, 0.5).into(),
+                    ..default()
+                });
+            }
+
+            parent
+                .spawn(TextBundle::from_section("", style))
+                .insert(LegendText { max: false });
+        });
+}
+
+/// Show the range of the field's magnitude at either end of the legend, for the field as the player wrote it
+fn legend_text_system(
+    range: Res<MagnitudeRange>,
+    mut legend_text: Query<(&mut Text, &LegendText)>,
+) {
+    if !range.is_changed() {
+        return
+    }
+
+    for (mut text, legend_text) in legend_text.iter_mut() {
+        let magnitude = if legend_text.max { range.max } else { range.min };
+        text.sections[0].value = format!("{:.2}", magnitude / FIELD_SCALE);
+    }
+}
+
+/// Toggle the heatmap with H, ignored while typing in an expression input. The heatmap is always hidden behind the
+/// texture render mode, which already colors the field by magnitude
+fn heatmap_visibility_system(
+    keys: Res<Input<KeyCode>>,
+    expression_inputs: Query<&ExpressionInput>,
+    render_mode: Res<State<FieldRenderMode>>,
+    mut settings: ResMut<HeatmapSettings>,
+    mut heatmap: Query<&mut Visibility, With<Heatmap>>,
+) {
+    if keys.just_pressed(KeyCode::H) && !expression_inputs.iter().any(|expression_input| expression_input.focused) {
+        settings.visible = !settings.visible;
+    }
+
+    heatmap.single_mut().is_visible = settings.visible && *render_mode.current() != FieldRenderMode::Texture;
+}
+
+/// Plugin for the magnitude heatmap and its legend
+pub struct HeatmapPlugin;
+
+impl Plugin for HeatmapPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MagnitudeRange::new());
+        app.insert_resource(HeatmapSettings { visible: true });
+        app.add_startup_system(heatmap_setup);
+        app.add_system(heatmap_update);
+        app.add_system(legend_text_system.after(heatmap_update));
+        app.add_system(heatmap_visibility_system);
+    }
+}
@@ -0,0 +1,227 @@
+use bevy::{
+    prelude::*,
+    asset::AssetServer,
+};
+
+use crate::{GameState, Level, LoadingState};
+use crate::solution::{FieldSolution, SolutionNode};
+use crate::ui::{ButtonXY, GradComponentButton, HintButton, grad_component_button_system};
+
+use crate::constants::{
+    MAX_HINTS,
+    HINT_BUTTON_COLOR,
+    HINT_TEXT_COLOR,
+    NORMAL_BUTTON_COLOR,
+    HOVERED_BUTTON_COLOR,
+    PRESSED_BUTTON_COLOR,
+    BUTTON_HEIGHT,
+    BUTTON_SPACING,
+};
+
+#[derive(Component)]
+/// Struct to label the text showing the hints revealed so far
+pub struct HintText;
+
+/// Number of hints a level has. A solution built from buttons gives a button, then its operations, then the whole
+/// field. A typed solution can only be given as a whole. Levels without a solution have no hints
+pub fn hint_count(level: &Level) -> u32 {
+    match &level.solution {
+        Some(FieldSolution::Buttons { .. }) => MAX_HINTS,
+        Some(FieldSolution::Typed { .. } | FieldSolution::Potential(_)) => 1,
+        None => 0,
+    }
+}
+
+/// Buttons to highlight once some hints are revealed, as (x button ids, y button ids). The first hint points to one
+/// button of the solution, and revealing the whole field points to all of them
+pub fn hinted_buttons(level: &Level, hints_shown: u32) -> (Vec<u32>, Vec<u32>) {
+    let (x, y) = match &level.solution {
+        Some(FieldSolution::Buttons { x, y }) => (
+            x.as_ref().map(SolutionNode::function_ids).unwrap_or_default(),
+            y.as_ref().map(SolutionNode::function_ids).unwrap_or_default(),
+        ),
+        _ => return (Vec::new(), Vec::new()), // typed solutions don't use the buttons
+    };
+
+    match hints_shown {
+        0 => (Vec::new(), Vec::new()),
+        shown if shown < MAX_HINTS => match x.first() { // one button, from the x component if it has any
+            Some(id) => (vec![*id], Vec::new()),
+            None => (Vec::new(), y.into_iter().take(1).collect()),
+        },
+        _ => (x, y),
+    }
+}
+
+/// Sentence naming the operations one component of a solution joins its functions with, in the order they're used
+fn operations_text(dimension: &str, component: &Option<SolutionNode>) -> String {
+    let operations = component.as_ref().map(SolutionNode::operations).unwrap_or_default();
+
+    match (component, operations.is_empty()) {
+        (None, _) => format!("{} uses no functions", dimension),
+        (Some(_), true) => format!("{} uses a single function", dimension),
+        (Some(_), false) => format!(
+            "{} joins its functions with {}",
+            dimension, operations.iter().map(|operation| operation.label()).collect::<Vec<&str>>().join(" then "),
+        ),
+    }
+}
+
+/// Text of the hints revealed so far for a level, one line per hint
+pub fn hint_text(level: &Level, hints_shown: u32) -> String {
+    let solution = match &level.solution {
+        Some(solution) => solution,
+        None => return String::new(),
+    };
+
+    let count = hint_count(level);
+    let mut lines = Vec::new();
+
+    // whole field, as the player would see it
+    let field = match (solution, solution.to_gradient(level)) {
+        (FieldSolution::Potential(potential), _) => format!("f = {}", potential),
+        (_, Ok(gradient)) => format!("x = {}, y = {}", gradient.x_text(), gradient.y_text()),
+        (_, Err(error)) => error,
+    };
+
+    match solution {
+        FieldSolution::Buttons { x, y } => {
+            if hints_shown >= 1 {
+                let (x_ids, y_ids) = hinted_buttons(level, 1);
+                let button = match (x_ids.first(), y_ids.first()) {
+                    (Some(id), _) => format!("the x button \"{}\"", level.x_functions[*id as usize].0),
+                    (None, Some(id)) => format!("the y button \"{}\"", level.y_functions[*id as usize].0),
+                    (None, None) => String::from("no buttons at all"),
+                };
+                lines.push(format!("Hint 1/{}: a solution uses {}", count, button));
+            }
+            if hints_shown >= 2 {
+                lines.push(format!("Hint 2/{}: {}, and {}", count, operations_text("x", x), operations_text("y", y)));
+            }
+            if hints_shown >= 3 {
+                lines.push(format!("Hint 3/{}: {}", count, field));
+            }
+        },
+        FieldSolution::Typed { .. } | FieldSolution::Potential(_) => {
+            if hints_shown >= 1 {
+                lines.push(format!("Hint 1/{}: {}", count, field));
+            }
+        },
+    }
+
+    lines.join("\n")
+}
+
+/// Spawn the hint text, under the buttons at the top left
+fn hint_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(BUTTON_SPACING),
+                    top: Val::Px(BUTTON_HEIGHT + 2.*BUTTON_SPACING),
+                    ..default()
+                },
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/tahoma.ttf"),
+                        font_size: 20.0,
+                        color: HINT_TEXT_COLOR,
+                    },
+                ))
+                .insert(HintText);
+        });
+}
+
+/// Reveal the next hint for the level with the hint button
+fn hint_button_system(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<HintButton>),
+    >,
+    mut game_state: Query<&mut GameState>,
+) {
+    for (interaction, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                *color = PRESSED_BUTTON_COLOR.into(); // change button color
+
+                let mut game_state = game_state.single_mut();
+                if game_state.hints_shown < hint_count(&game_state.level_info[game_state.current_level as usize]) {
+                    game_state.hints_shown += 1;
+                }
+            },
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON_COLOR.into(); // change color on hover
+            },
+            Interaction::None => {
+                *color = NORMAL_BUTTON_COLOR.into(); // change color back on no hover/interaction
+            },
+        }
+    }
+}
+
+/// Update the hint text whenever a hint is revealed or the level changes. The hint button is hidden on levels
+/// without hints
+fn hint_text_system(
+    game_state: Query<&GameState>,
+    mut text: Query<&mut Text, With<HintText>>,
+    mut hint_button: Query<&mut Style, With<HintButton>>,
+    mut shown: Local<Option<(u32, u32)>>, // level and number of hints the text was last written for
+) {
+    let game_state = game_state.single();
+
+    if *shown == Some((game_state.current_level, game_state.hints_shown)) {
+        return
+    }
+
+    let level = &game_state.level_info[game_state.current_level as usize];
+
+    text.single_mut().sections[0].value = hint_text(level, game_state.hints_shown);
+    hint_button.single_mut().display = if hint_count(level) > 0 { Display::Flex } else { Display::None };
+
+    *shown = Some((game_state.current_level, game_state.hints_shown));
+}
+
+/// Color the function buttons the hints point to, while they aren't pressed or hovered
+fn hint_highlight_system(
+    game_state: Query<&GameState>,
+    mut buttons: Query<(&Interaction, &mut BackgroundColor, &GradComponentButton)>,
+) {
+    let game_state = game_state.single();
+    let (x_ids, y_ids) = hinted_buttons(&game_state.level_info[game_state.current_level as usize], game_state.hints_shown);
+
+    for (interaction, mut color, button) in buttons.iter_mut() {
+        let ids = match button.xy {
+            ButtonXY::X => &x_ids,
+            ButtonXY::Y => &y_ids,
+        };
+
+        if ids.contains(&button.id) && !button.used && *interaction == Interaction::None {
+            *color = HINT_BUTTON_COLOR.into();
+        }
+    }
+}
+
+/// Plugin for hints from each level's known solution
+pub struct HintPlugin;
+
+impl Plugin for HintPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(hint_setup);
+        app.add_system_set(
+            SystemSet::on_update(LoadingState::Loaded) // hints need level info
+                .with_system(hint_button_system)
+                .with_system(hint_text_system)
+                .with_system(hint_highlight_system.after(grad_component_button_system)) // drawn over the usual button color
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use bevy::prelude::*;
+
+use crate::{LoadingState, Simulating};
+use crate::gradient_field::{FieldNode, Gradient, GradientOperation, GradientOperationState};
+use crate::level::LoadLevel;
+use crate::ui::{
+    ButtonXY,
+    ExpressionInput,
+    FieldEntryMode,
+    GradComponentButton,
+    HistoryButton,
+    grad_component_button_system,
+    group_button_system,
+    operation_state_button_handling,
+};
+
+use crate::constants::{
+    FIELD_HISTORY_LENGTH,
+    NORMAL_BUTTON_COLOR,
+    HOVERED_BUTTON_COLOR,
+    PRESSED_BUTTON_COLOR,
+    NORMAL_BUTTON_TEXT_COLOR,
+    PRESSED_BUTTON_TEXT_COLOR,
+};
+
+#[derive(Clone, Debug)]
+/// A change the player made while building the field from the function buttons
+pub enum FieldCommand {
+    ChangeComponent { // adding, removing or grouping functions in one component
+        xy: ButtonXY, // component changed
+        before: Option<FieldNode>, // component before the change
+        after: Option<FieldNode>, // component after the change
+    },
+    SelectOperation { // choosing the operation new functions are added with
+        before: GradientOperation, // operation selected before the change
+        after: GradientOperation, // operation selected after the change
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Whether to go back or forward through the field history
+pub enum HistoryAction {
+    Undo, // reverse the last change
+    Redo, // make the last undone change again
+}
+
+/// Event asking for the last change to the field to be undone or redone
+pub struct UndoRedo {
+    pub action: HistoryAction, // which way to go through the history
+}
+
+#[derive(Resource)]
+/// Changes made to the field from the function buttons since the level started or the entry mode last switched
+pub struct FieldHistory {
+    undo: Vec<FieldCommand>, // changes that can be undone, most recent last
+    redo: Vec<FieldCommand>, // changes that were undone and can be made again, most recently undone last
+}
+
+impl FieldHistory {
+    /// New method. Starts with nothing to undo or redo
+    pub fn new() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    /// Record a change to one component. Changes that leave the component as it was (like grouping it twice) aren't
+    /// recorded
+    pub fn record_component(&mut self, xy: ButtonXY, before: Option<FieldNode>, after: Option<FieldNode>) {
+        if before != after {
+            self.record(FieldCommand::ChangeComponent { xy, before, after });
+        }
+    }
+
+    /// Record choosing a different operation
+    pub fn record_operation(&mut self, before: GradientOperation, after: GradientOperation) {
+        if before != after {
+            self.record(FieldCommand::SelectOperation { before, after });
+        }
+    }
+
+    /// Add a new change. Anything undone can no longer be redone, and the oldest change is dropped once there are
+    /// more than FIELD_HISTORY_LENGTH
+    fn record(&mut self, command: FieldCommand) {
+        self.undo.push(command);
+        if self.undo.len() > FIELD_HISTORY_LENGTH {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Whether there is a change to undo or redo
+    pub fn can(&self, action: HistoryAction) -> bool {
+        match action {
+            HistoryAction::Undo => !self.undo.is_empty(),
+            HistoryAction::Redo => !self.redo.is_empty(),
+        }
+    }
+
+    /// Take the change to undo or redo, moving it to the other side so that it can be reversed again
+    pub fn step(&mut self, action: HistoryAction) -> Option<FieldCommand> {
+        let (from, to) = match action {
+            HistoryAction::Undo => (&mut self.undo, &mut self.redo),
+            HistoryAction::Redo => (&mut self.redo, &mut self.undo),
+        };
+
+        let command = from.pop()?;
+        to.push(command.clone());
+        Some(command)
+    }
+
+    /// Forget every change, as when the level or entry mode changes
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+/// One component of the gradient
+pub fn component(gradient: &Gradient, xy: ButtonXY) -> &Option<FieldNode> {
+    match xy {
+        ButtonXY::X => &gradient.x_component,
+        ButtonXY::Y => &gradient.y_component,
+    }
+}
+
+/// Ask to undo with Ctrl+Z, and to redo with Ctrl+Y or Ctrl+Shift+Z. Cmd works in place of Ctrl. Ignored while
+/// typing in an expression input
+fn history_keyboard_system(
+    keys: Res<Input<KeyCode>>,
+    expression_inputs: Query<&ExpressionInput>,
+    mut undo_redo: EventWriter<UndoRedo>,
+) {
+    if expression_inputs.iter().any(|expression_input| expression_input.focused) {
+        return
+    }
+
+    if !keys.any_pressed([KeyCode::LControl, KeyCode::RControl, KeyCode::LWin, KeyCode::RWin]) {
+        return
+    }
+
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+
+    if keys.just_pressed(KeyCode::Y) || (shift && keys.just_pressed(KeyCode::Z)) {
+        undo_redo.send(UndoRedo { action: HistoryAction::Redo });
+    } else if keys.just_pressed(KeyCode::Z) {
+        undo_redo.send(UndoRedo { action: HistoryAction::Undo });
+    }
+}
+
+/// Update system for the undo and redo buttons. Their text is dimmed while there is nothing to undo or redo, updated
+/// only when the history changes
+fn history_button_system(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &HistoryButton),
+        (Changed<Interaction>, With<Button>),
+    >,
+    buttons: Query<(&Children, &HistoryButton)>,
+    mut text_query: Query<&mut Text>,
+    history: Res<FieldHistory>,
+    mut undo_redo: EventWriter<UndoRedo>,
+) {
+    for (interaction, mut color, button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                *color = PRESSED_BUTTON_COLOR.into(); // change button color
+
+                undo_redo.send(UndoRedo { action: button.action });
+            },
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON_COLOR.into(); // change color on hover
+            },
+            Interaction::None => {
+                *color = NORMAL_BUTTON_COLOR.into(); // change color back on no hover/interaction
+            },
+        }
+    }
+
+    if !history.is_changed() {
+        return
+    }
+
+    for (children, button) in buttons.iter() {
+        let mut text = text_query.get_mut(children[0]).unwrap(); // get text of button
+        let color = if history.can(button.action) { NORMAL_BUTTON_TEXT_COLOR } else { PRESSED_BUTTON_TEXT_COLOR };
+        if text.sections[0].style.color != color {
+            text.sections[0].style.color = color;
+        }
+    }
+}
+
+/// Undo or redo changes to the field. The selected operation and the function buttons are put back to match, and
+/// changing the field stops simulating
+fn undo_redo_system(
+    mut undo_redo: EventReader<UndoRedo>,
+    mut history: ResMut<FieldHistory>,
+    mut gradient: Query<&mut Gradient>,
+    mut grad_buttons: Query<(&Children, &mut BackgroundColor, &mut GradComponentButton)>,
+    mut text_query: Query<&mut Text>,
+    mut operation_state: ResMut<State<GradientOperationState>>,
+    mut simulating_state: ResMut<State<Simulating>>,
+) {
+    let mut gradient = gradient.single_mut(); // get gradient
+    let mut field_changed = false;
+
+    for UndoRedo { action } in undo_redo.iter() {
+        let command = match history.step(*action) {
+            Some(command) => command,
+            None => continue, // nothing to undo or redo
+        };
+
+        let undo = *action == HistoryAction::Undo;
+
+        match command {
+            FieldCommand::ChangeComponent { xy, before, after } => {
+                gradient.potential = None; // field built from buttons never comes from a potential
+                match xy {
+                    ButtonXY::X => gradient.x_component = if undo { before } else { after },
+                    ButtonXY::Y => gradient.y_component = if undo { before } else { after },
+                }
+                field_changed = true;
+            },
+            FieldCommand::SelectOperation { before, after } => {
+                let state = GradientOperationState::from_operation(if undo { before } else { after });
+                if *operation_state.current() != state {
+                    operation_state.overwrite_set(state).unwrap(); // update state
+                }
+            },
+        }
+    }
+
+    if !field_changed {
+        return
+    }
+
+    if *simulating_state.current() == Simulating::Simulating {
+        simulating_state.overwrite_set(Simulating::NotSimulating).unwrap(); // changing the field stops simulating
+    }
+
+    // a button is used exactly when its function is in the restored field
+    let x_ids = gradient.x_component.as_ref().map(FieldNode::function_ids).unwrap_or_default();
+    let y_ids = gradient.y_component.as_ref().map(FieldNode::function_ids).unwrap_or_default();
+
+    for (children, mut background_color, mut grad_component_button) in grad_buttons.iter_mut() {
+        let mut text = text_query.get_mut(children[0]).unwrap();
+
+        grad_component_button.used = match grad_component_button.xy {
+            ButtonXY::X => x_ids.contains(&grad_component_button.id),
+            ButtonXY::Y => y_ids.contains(&grad_component_button.id),
+        };
+
+        if grad_component_button.used {
+            *background_color = PRESSED_BUTTON_COLOR.into(); // update button background
+            text.sections[0].style.color = PRESSED_BUTTON_TEXT_COLOR; // update button text color
+        } else {
+            *background_color = NORMAL_BUTTON_COLOR.into();
+            text.sections[0].style.color = NORMAL_BUTTON_TEXT_COLOR;
+        }
+    }
+}
+
+/// Forget the field history when a level is loaded or the entry mode switches, since the field is cleared
+fn history_reset_system(
+    mut load_level: EventReader<LoadLevel>,
+    entry_mode: Res<State<FieldEntryMode>>,
+    mut history: ResMut<FieldHistory>,
+) {
+    if load_level.iter().count() > 0 || entry_mode.is_changed() {
+        history.clear();
+    }
+}
+
+/// Plugin for undoing and redoing changes to the field
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FieldHistory::new());
+        app.add_event::<UndoRedo>();
+        app.add_system(history_keyboard_system);
+        app.add_system(history_button_system);
+        app.add_system(history_reset_system);
+        app.add_system_set(
+            SystemSet::on_update(LoadingState::Loaded) // function buttons are set up from level info
+                .with_system(
+                    undo_redo_system // runs last so that its state changes replace ones the buttons queued this frame
+                        .after(grad_component_button_system)
+                        .after(group_button_system)
+                        .after(operation_state_button_handling)
+                )
+        );
+    }
+}
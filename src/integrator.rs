@@ -0,0 +1,194 @@
+use serde::Deserialize;
+
+use crate::constants::{RK45_TOLERANCE, RK45_MAX_STEPS};
+
+/// Numerical method used to move the player through the field. Selected per level in the level file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Default)]
+pub enum Integrator {
+    Euler, // explicit Euler, one field evaluation per step
+    Midpoint, // explicit midpoint, two field evaluations per step
+    #[default]
+    Rk4, // classic fourth order Runge-Kutta, four field evaluations per step
+    Rk45, // adaptive Dormand-Prince 5(4), splits the step until the estimated error is below RK45_TOLERANCE
+}
+
+impl Integrator {
+    /// Move a position through a velocity field for a time dt
+    pub fn step(&self, velocity: impl Fn(f32, f32) -> (f32, f32), position: (f32, f32), dt: f32) -> (f32, f32) {
+        match self {
+            Integrator::Euler => offset(position, velocity(position.0, position.1), dt),
+            Integrator::Midpoint => {
+                let k1 = velocity(position.0, position.1);
+                let midpoint = offset(position, k1, dt / 2.);
+                offset(position, velocity(midpoint.0, midpoint.1), dt)
+            },
+            Integrator::Rk4 => rk4_step(&velocity, position, dt),
+            Integrator::Rk45 => rk45_integrate(&velocity, position, dt),
+        }
+    }
+}
+
+/// Position moved along a velocity for a time dt
+fn offset(position: (f32, f32), velocity: (f32, f32), dt: f32) -> (f32, f32) {
+    (position.0 + velocity.0 * dt, position.1 + velocity.1 * dt)
+}
+
+/// Position moved along a weighted sum of velocities for a time dt
+fn offset_weighted(position: (f32, f32), velocities: &[(f32, f32)], weights: &[f32], dt: f32) -> (f32, f32) {
+    let mut moved = position;
+    for (velocity, weight) in velocities.iter().zip(weights.iter()) {
+        moved.0 += velocity.0 * weight * dt;
+        moved.1 += velocity.1 * weight * dt;
+    }
+    moved
+}
+
+/// Single classic Runge-Kutta step
+fn rk4_step(velocity: &impl Fn(f32, f32) -> (f32, f32), position: (f32, f32), dt: f32) -> (f32, f32) {
+    let k1 = velocity(position.0, position.1);
+    let p2 = offset(position, k1, dt / 2.);
+    let k2 = velocity(p2.0, p2.1);
+    let p3 = offset(position, k2, dt / 2.);
+    let k3 = velocity(p3.0, p3.1);
+    let p4 = offset(position, k3, dt);
+    let k4 = velocity(p4.0, p4.1);
+
+    offset_weighted(position, &[k1, k2, k3, k4], &[1. / 6., 1. / 3., 1. / 3., 1. / 6.], dt)
+}
+
+// Dormand-Prince coefficients
+const DP_A: [[f32; 6]; 6] = [
+    [1. / 5., 0., 0., 0., 0., 0.],
+    [3. / 40., 9. / 40., 0., 0., 0., 0.],
+    [44. / 45., -56. / 15., 32. / 9., 0., 0., 0.],
+    [19372. / 6561., -25360. / 2187., 64448. / 6561., -212. / 729., 0., 0.],
+    [9017. / 3168., -355. / 33., 46732. / 5247., 49. / 176., -5103. / 18656., 0.],
+    [35. / 384., 0., 500. / 1113., 125. / 192., -2187. / 6784., 11. / 84.],
+];
+const DP_FIFTH_ORDER: [f32; 7] = [35. / 384., 0., 500. / 1113., 125. / 192., -2187. / 6784., 11. / 84., 0.];
+const DP_FOURTH_ORDER: [f32; 7] = [5179. / 57600., 0., 7571. / 16695., 393. / 640., -92097. / 339200., 187. / 2100., 1. / 40.];
+
+/// Single Dormand-Prince step. Returns the fifth order position and the estimated error of the step
+fn dormand_prince_step(velocity: &impl Fn(f32, f32) -> (f32, f32), position: (f32, f32), h: f32) -> ((f32, f32), f32) {
+    let mut k = [(0., 0.); 7];
+    k[0] = velocity(position.0, position.1);
+
+    for stage in 0..6 {
+        let stage_position = offset_weighted(position, &k[..=stage], &DP_A[stage][..=stage], h);
+        k[stage + 1] = velocity(stage_position.0, stage_position.1);
+    }
+
+    let fifth = offset_weighted(position, &k, &DP_FIFTH_ORDER, h);
+    let fourth = offset_weighted(position, &k, &DP_FOURTH_ORDER, h);
+
+    (fifth, ((fifth.0 - fourth.0).powi(2) + (fifth.1 - fourth.1).powi(2)).sqrt())
+}
+
+/// Integrate over a time dt with adaptive Dormand-Prince steps. Steps are shrunk until their error is below
+/// RK45_TOLERANCE, but never below dt / RK45_MAX_STEPS so a single call can't take forever
+fn rk45_integrate(velocity: &impl Fn(f32, f32) -> (f32, f32), position: (f32, f32), dt: f32) -> (f32, f32) {
+    let min_step = dt.abs() / RK45_MAX_STEPS as f32;
+
+    let mut position = position;
+    let mut remaining = dt; // time left to integrate
+    let mut h = dt; // try the whole step first
+
+    while remaining.abs() > dt.abs() * f32::EPSILON {
+        if h.abs() > remaining.abs() { // don't step past the end
+            h = remaining;
+        }
+
+        let (next, error) = dormand_prince_step(velocity, position, h);
+
+        if error <= RK45_TOLERANCE || h.abs() <= min_step || !error.is_finite() { // accept step
+            position = next;
+            remaining -= h;
+        }
+
+        // grow or shrink the next step based on how the error compares to the tolerance
+        let factor = if error > 0. && error.is_finite() {
+            (0.9 * (RK45_TOLERANCE / error).powf(0.2)).clamp(0.2, 5.)
+        } else {
+            5.
+        };
+        h = (h * factor).abs().max(min_step).copysign(dt);
+    }
+
+    position
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Rotation about the origin at one radian per second, so exact paths are circles
+    fn rotation(x: f32, y: f32) -> (f32, f32) {
+        (-y, x)
+    }
+
+    /// Distance from the origin after one full turn from (1, 0) in fixed steps of dt
+    fn radius_after_turn(integrator: Integrator, dt: f32) -> f32 {
+        let steps = (2. * std::f32::consts::PI / dt).round() as u32;
+        let mut position = (1., 0.);
+        for _ in 0..steps {
+            position = integrator.step(rotation, position, dt);
+        }
+        (position.0.powi(2) + position.1.powi(2)).sqrt()
+    }
+
+    #[test]
+    fn radius_drifts_less_for_higher_order_integrators() {
+        let drift = |integrator| (radius_after_turn(integrator, 0.02) - 1.).abs();
+        let (euler, midpoint, rk4, rk45) = (drift(Integrator::Euler), drift(Integrator::Midpoint), drift(Integrator::Rk4), drift(Integrator::Rk45));
+
+        assert!(euler < 0.1, "Euler drifted {}", euler);
+        assert!(midpoint < 1e-3, "midpoint drifted {}", midpoint);
+        assert!(rk4 < 1e-4, "RK4 drifted {}", rk4);
+        assert!(rk45 < 1e-3, "RK45 drifted {}", rk45);
+        assert!(midpoint < euler && rk4 < midpoint, "drift should shrink with order: {} {} {}", euler, midpoint, rk4);
+    }
+
+    #[test]
+    fn euler_spirals_outwards_on_a_rotation() {
+        assert!(radius_after_turn(Integrator::Euler, 0.02) > 1.); // each step moves along the tangent, away from the circle
+    }
+
+    #[test]
+    fn rk45_meets_its_tolerance_on_a_long_step() {
+        let dt = 1.5;
+        let (_, one_step_error) = dormand_prince_step(&rotation, (1., 0.), dt);
+        assert!(one_step_error > RK45_TOLERANCE, "step should be long enough to need splitting");
+
+        let position = Integrator::Rk45.step(rotation, (1., 0.), dt);
+        let error = ((position.0 - dt.cos()).powi(2) + (position.1 - dt.sin()).powi(2)).sqrt();
+        assert!(error <= RK45_TOLERANCE, "error {} is above the tolerance", error);
+    }
+
+    #[test]
+    fn rk45_terminates_when_the_error_is_not_finite() {
+        let evaluations = Cell::new(0);
+        let field = |x: f32, _: f32| {
+            evaluations.set(evaluations.get() + 1);
+            if x > 1.01 { (f32::NAN, f32::INFINITY) } else { (1., 0.) }
+        };
+
+        Integrator::Rk45.step(field, (1., 0.), 1.);
+        assert!(evaluations.get() <= 7 * 2 * RK45_MAX_STEPS, "took {} field evaluations", evaluations.get());
+
+        let position = Integrator::Rk45.step(|_, _| (f32::NAN, f32::NAN), (0., 0.), 1.);
+        assert!(position.0.is_nan()); // gives up on the field rather than looping
+    }
+
+    #[test]
+    fn rk45_takes_at_most_its_step_limit_on_a_stiff_field() {
+        let steps = Cell::new(0);
+        let field = |x: f32, y: f32| {
+            steps.set(steps.get() + 1);
+            (1e4 * y, -1e4 * x) // fast rotation, whose error never meets the tolerance at the smallest step
+        };
+
+        Integrator::Rk45.step(field, (1., 0.), 1.);
+        assert!(steps.get() <= 7 * 2 * RK45_MAX_STEPS, "took {} field evaluations", steps.get());
+    }
+}
@@ -3,10 +3,21 @@ use bevy::{
     asset::AssetServer,
 };
 
-use crate::ui::GradComponentButton;
-use crate::{GameState, Player, Simulating, Gradient, NewLevelText, ButtonXY};
+use crate::ui::{
+    ExpressionInput,
+    FieldEntryMode,
+    grad_component_button_system,
+    group_button_system,
+    expression_input_typing_system,
+};
+use crate::{GameState, Player, Simulating, Gradient, NewLevelText, LoadingState};
+use crate::field_analysis::FieldAnalysis;
+use crate::simulation::{self, SimulationClock};
+use crate::solution::FieldSolution;
+use crate::score::Score;
+use crate::level_select::MenuState;
 
-use crate::constants::{ENDING_LOCATION_ERROR, PORTAL_SCALE, MAX_GAS_CANS, GAS_CAN_SCALE, NORMAL_BUTTON_COLOR, NORMAL_BUTTON_TEXT_COLOR};
+use crate::constants::{PORTAL_SCALE, MAX_GAS_CANS, GAS_CAN_SCALE, FIXED_TIMESTEP};
 
 #[derive(Component)]
 /// struct to label ending location sprite 
@@ -19,6 +30,18 @@ pub struct GasCan {
     pub index: u32, // specific index of this gas can 
 }
 
+/// Event sent when the player finishes a level
+pub struct LevelCompleted {
+    pub level: u32, // index of the level that was finished
+    pub score: Score, // how well the level was finished
+    pub field: FieldSolution, // field that finished the level
+}
+
+/// Event asking for a level to be played from the start, with the field cleared and the buttons reset
+pub struct LoadLevel {
+    pub level: u32, // index of the level to load
+}
+
 /// load ending location sprite and place in world 
 fn ending_location_setup(
     mut commands: Commands,
@@ -48,69 +71,91 @@ fn ending_location_update(
     }
 }
 
-fn level_update_system(
-    mut player: Query<(&Player, &Transform)>,
-    mut game_state: Query<&mut GameState>,
+/// Finish the level once the player reaches the end. Moves on to the next level, or opens the level select after the last one
+pub fn level_update_system(
+    player: Query<&Player>,
+    game_state: Query<&GameState>,
     mut simulating_state: ResMut<State<Simulating>>,
-    mut gradient: Query<&mut Gradient>,
-    mut new_level_text: Query<&mut NewLevelText>,
-    mut text_query: Query<&mut Text>,
-    mut grad_buttons: Query<(&Children, &mut BackgroundColor, &mut GradComponentButton)>,
+    mut menu_state: ResMut<State<MenuState>>,
+    gradient: Query<&Gradient>,
+    expression_inputs: Query<&ExpressionInput>,
+    field_analysis: Res<FieldAnalysis>,
+    clock: Res<SimulationClock>,
+    entry_mode: Res<State<FieldEntryMode>>,
+    mut level_completed: EventWriter<LevelCompleted>,
+    mut load_level: EventWriter<LoadLevel>,
 ) {
-    let (_, player_transform) = player.single_mut(); // should be exclusively 1 player
+    if *simulating_state.current() != Simulating::Simulating { // a level is only finished by simulating
+        return
+    }
 
-    let mut game_state = game_state.single_mut();
-    let mut gradient = gradient.single_mut();
-    let mut new_level_text = new_level_text.single_mut();
+    let player = player.single(); // should be exclusively 1 player
+    let game_state = game_state.single();
+    let gradient = gradient.single();
+
+    let field_allowed = field_analysis.conservative || !game_state.level_info[game_state.current_level as usize].require_conservative; // some levels need a conservative field
 
-    let distance_from_end_x = game_state.level_info[game_state.current_level as usize].end_location.0 - player_transform.translation.x; 
-    let distance_from_end_y = game_state.level_info[game_state.current_level as usize].end_location.1 - player_transform.translation.y;
+    let reached_end = simulation::reached_end(&game_state.level_info[game_state.current_level as usize], (player.x, player.y), &game_state.gas_collected); // checked on the simulated position, not the drawn one
 
-    let distance_from_end = (distance_from_end_x.powi(2) + distance_from_end_y.powi(2)).sqrt();
+    if reached_end && field_allowed { // if within allowable error from end, collected all the gas and used an allowed field
+        simulating_state.overwrite_set(Simulating::NotSimulating).unwrap(); // stop simulating on level end, replacing a change the buttons queued this frame
 
-    if full_gas(&game_state) && distance_from_end < ENDING_LOCATION_ERROR { // if within allowable error from end and collected all the gas
-        match simulating_state.current() { // stop simulating on level end
-            Simulating::NotSimulating => {},
-            Simulating::Simulating => {
-                simulating_state.set(Simulating::NotSimulating).unwrap(); // stop simulating 
-            },
+        level_completed.send(LevelCompleted {
+            level: game_state.current_level,
+            score: Score::new(gradient, clock.steps as f32 * FIXED_TIMESTEP, clock.distance, game_state.hints_shown),
+            field: FieldSolution::from_field(entry_mode.current(), gradient, expression_inputs.iter()),
+        });
+
+        if game_state.current_level == game_state.level_info.len() as u32 - 1 { // if last level, pick what to play next
+            if *menu_state.current() != MenuState::LevelSelect {
+                menu_state.overwrite_set(MenuState::LevelSelect).unwrap();
+            }
+        } else {
+            load_level.send(LoadLevel { level: game_state.current_level + 1 });
         }
+    }
+}
 
-        gradient.clear_field(); // clear gradient field
+/// Load levels asked for with LoadLevel. Clears the field, the typed text and the hints, and shows the level number. The function
+/// buttons are reset separately, by the UI
+pub fn load_level_system(
+    mut load_level: EventReader<LoadLevel>,
+    mut game_state: Query<&mut GameState>,
+    mut simulating_state: ResMut<State<Simulating>>,
+    mut gradient: Query<&mut Gradient>,
+    mut new_level_text: Query<&mut NewLevelText>,
+    mut expression_inputs: Query<&mut ExpressionInput>,
+) {
+    let level = match load_level.iter().last() { // only the last request matters
+        Some(load_level) => load_level.level,
+        None => return,
+    };
 
-        if game_state.current_level == game_state.level_info.len() as u32 - 1 { // if last level
-            game_state.current_level = 0; // reset to first level
+    let mut game_state = game_state.single_mut();
 
-            // update new level text to fade in and out with new level 
-            new_level_text.fade_in = true;
-            new_level_text.fade_out = false;
-            new_level_text.level = game_state.current_level + 1;
-        } else {
-            game_state.current_level += 1; // increment level
+    if level as usize >= game_state.level_info.len() {
+        warn!("no level {} to load", level + 1);
+        return
+    }
 
-            // update new level text to fade in and out with new level 
-            new_level_text.fade_in = true;
-            new_level_text.fade_out = false;
-            new_level_text.level = game_state.current_level + 1;
-        }
+    if *simulating_state.current() == Simulating::Simulating {
+        simulating_state.overwrite_set(Simulating::NotSimulating).unwrap(); // level starts from the beginning, even if already asked to stop
+    }
 
-        for (children, mut background_color, mut grad_component_button) in grad_buttons.iter_mut() { // reset gradient buttons
-            let mut text = text_query.get_mut(children[0]).unwrap();
-            
-            *background_color = NORMAL_BUTTON_COLOR.into(); // update button background 
-            text.sections[0].style.color = NORMAL_BUTTON_TEXT_COLOR; // update button text color
-            grad_component_button.used = false; // update button used
+    game_state.current_level = level;
+    game_state.hints_shown = 0; // hints start over on every level
 
-            match grad_component_button.xy {
-                ButtonXY::X => {
-                    text.sections[0].value = game_state.level_info[game_state.current_level as usize].x_functions[grad_component_button.id as usize].0.clone(); // update button text
-                },
-                ButtonXY::Y => {
-                    text.sections[0].value = game_state.level_info[game_state.current_level as usize].y_functions[grad_component_button.id as usize].0.clone(); // update button text
-                },
-            }
+    gradient.single_mut().clear_field(); // clear gradient field
 
-        }
+    // update new level text to fade in and out with new level 
+    let mut new_level_text = new_level_text.single_mut();
+    new_level_text.fade_in = true;
+    new_level_text.fade_out = false;
+    new_level_text.level = game_state.current_level + 1;
+
+    for mut expression_input in expression_inputs.iter_mut() { // reset typed field
+        expression_input.text.clear();
+        expression_input.error = None;
     }
 }
 
@@ -137,12 +182,10 @@ pub fn gas_setup(
 /// update gas sprite positions based on current level 
 pub fn gas_update(
     mut query: Query<(&mut Transform, &mut Visibility, &mut GasCan)>,
-    player: Query<&Player>,
     mut game_state: Query<&mut GameState>,
     simulating_state: Res<State<Simulating>>
 ) {
     let mut game_state = game_state.single_mut(); // get game state
-    let player = player.single(); // get player
 
     if game_state.gas_collected.len() != game_state.level_info[game_state.current_level as usize].gas_locations.len() { // if gas collected is not the same length as gas locations
         game_state.gas_collected = vec![0; game_state.level_info[game_state.current_level as usize].gas_locations.len()]; // reset gas collected
@@ -174,16 +217,8 @@ pub fn gas_update(
                     transform.translation.x = gas_can_position.0 as f32; // set gas can position
                     transform.translation.y = gas_can_position.1 as f32;
 
-                    let distance_from_player_x = gas_can_position.0 as f32 - player.x; // get distance from player
-                    let distance_from_player_y = gas_can_position.1 as f32 - player.y;
-
-                    let distance_from_player = (distance_from_player_x.powi(2) + distance_from_player_y.powi(2)).sqrt();
-
-                    if distance_from_player < ENDING_LOCATION_ERROR {
+                    if game_state.gas_collected[gas_can.index as usize] == 1 { // collected during a fixed simulation step
                         gas_can.collected = true; // set gas can collected state 
-
-                        game_state.gas_collected[gas_can.index as usize] = 1; // set gas can collected state in game state
-
                     }
 
                     if gas_can.collected {
@@ -200,26 +235,26 @@ pub fn gas_update(
     }
 }
 
-/// internal function for whether or not the player has collected all the gas 
-pub fn full_gas(
-    game_state: &GameState, // raw game state 
-) -> bool {
-    if game_state.gas_collected.iter().sum::<u32>() == game_state.level_info[game_state.current_level as usize].gas_locations.len() as u32 { // if all gas was collected 
-        return true
-    } else {
-        return false
-    }
-}
-
 /// Plugin for controlling level logic 
 pub struct LevelPlugin;
 
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(level_update_system);
+        app.add_event::<LevelCompleted>();
+        app.add_event::<LoadLevel>();
         app.add_startup_system(ending_location_setup);
-        app.add_system(ending_location_update);
         app.add_startup_system(gas_setup);
-        app.add_system(gas_update);
+        app.add_system_set(
+            SystemSet::on_update(LoadingState::Loaded) // these systems need level info
+                .with_system(
+                    level_update_system // runs after the systems that stop simulating when the field changes, so its state change comes last
+                        .after(grad_component_button_system)
+                        .after(group_button_system)
+                        .after(expression_input_typing_system)
+                )
+                .with_system(load_level_system.after(level_update_system))
+                .with_system(ending_location_update)
+                .with_system(gas_update)
+        );
     }
 }
\ No newline at end of file
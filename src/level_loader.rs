@@ -0,0 +1,280 @@
+use std::fmt;
+
+use bevy::{
+    prelude::*,
+    asset::{AssetLoader, LoadContext, LoadedAsset, BoxedFuture, AssetEvent},
+    reflect::TypeUuid,
+};
+use serde::Deserialize;
+
+use crate::{GameState, Level};
+use crate::expression::Expression;
+use crate::integrator::Integrator;
+use crate::save::SaveData;
+use crate::solution::FieldSolution;
+use crate::score;
+
+use crate::constants::{BUTTONS_PER_DIMENSION, MAX_GAS_CANS, LEVEL_FILE_PATH};
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+/// enum to store whether the level data has finished loading
+pub enum LoadingState {
+    Loading, // level file has not been loaded yet
+    Loaded, // level file loaded and copied into the game state
+}
+
+#[derive(Deserialize)]
+/// Raw contents of a level file, before validation
+struct LevelFile {
+    levels: Vec<LevelDescription>, // levels in the order they are played
+}
+
+#[derive(Deserialize)]
+/// Raw description of a single level, as written in the level file
+struct LevelDescription {
+    id: String, // name of the level that stays the same when levels are added or reordered, used to key saved progress
+    start_location: (f32, f32), // starting location
+    end_location: (f32, f32), // ending location
+    x_functions: Vec<String>, // strings for the functions available for x dimension
+    y_functions: Vec<String>, // strings for the functions available for y dimension
+    #[serde(default)]
+    gas_locations: Vec<(f32, f32)>, // locations of gas stops
+    tick_time: f32,
+    #[serde(default)]
+    require_conservative: bool, // whether the level can only be finished with a conservative field
+    #[serde(default)]
+    integrator: Integrator, // numerical method for moving the player, Rk4 if not given
+    #[serde(default)]
+    solution: Option<FieldSolution>, // intended solution, used as the level's par
+}
+
+#[derive(Debug)]
+/// Error for a level file that could not be turned into levels. Stores every problem found, per level
+pub struct LevelLoadError {
+    pub problems: Vec<(Option<usize>, String)>, // (level index if the problem belongs to a level, description of problem)
+}
+
+impl fmt::Display for LevelLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid level file")?;
+        for (level, problem) in self.problems.iter() {
+            match level {
+                Some(level) => write!(f, "\n  level {}: {}", level + 1, problem)?, // levels are shown to the player starting at 1
+                None => write!(f, "\n  {}", problem)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LevelLoadError {}
+
+#[derive(TypeUuid, Clone, Debug)]
+#[uuid = "8d1c2f5e-5a0b-4b73-9b0e-2f6b7f3a9c41"]
+/// Asset holding every level loaded from a level file
+pub struct LevelSet {
+    pub levels: Vec<Level>,
+}
+
+#[derive(Resource)]
+/// Handle to the level file used by the game
+pub struct LevelSetHandle(pub Handle<LevelSet>);
+
+/// Parse the functions for one dimension of a level into their string and expression pairs, recording any problems
+fn palette(
+    function_strings: &[String],
+    dimension: &str,
+    problems: &mut Vec<String>,
+) -> Vec<(String, Expression)> {
+    if function_strings.len() != BUTTONS_PER_DIMENSION as usize { // every button needs exactly one function
+        problems.push(format!("expected {} {} functions, found {}", BUTTONS_PER_DIMENSION, dimension, function_strings.len()));
+    }
+
+    let mut functions = Vec::new();
+    for function_string in function_strings.iter() {
+        match Expression::parse(function_string) {
+            Ok(function) => functions.push((function_string.clone(), function)),
+            Err(error) => problems.push(format!("invalid {} function \"{}\": {}", dimension, function_string, error)),
+        }
+    }
+    functions
+}
+
+/// Validate a raw level description and turn it into a level. Returns all problems with the level on failure
+fn validate_level(level_number: u32, description: LevelDescription) -> Result<Level, Vec<String>> {
+    let mut problems = Vec::new();
+
+    if description.id.trim().is_empty() {
+        problems.push(String::from("id must not be empty"));
+    }
+
+    let x_functions = palette(&description.x_functions, "x", &mut problems);
+    let y_functions = palette(&description.y_functions, "y", &mut problems);
+
+    if description.gas_locations.len() > MAX_GAS_CANS as usize {
+        problems.push(format!("at most {} gas locations are allowed, found {}", MAX_GAS_CANS, description.gas_locations.len()));
+    }
+
+    if !(description.tick_time > 0. && description.tick_time.is_finite()) {
+        problems.push(format!("tick_time must be a positive number, found {}", description.tick_time));
+    }
+
+    let locations = [description.start_location, description.end_location].into_iter().chain(description.gas_locations.iter().copied());
+    for location in locations {
+        if !(location.0.is_finite() && location.1.is_finite()) {
+            problems.push(format!("location ({}, {}) is not finite", location.0, location.1));
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(problems)
+    }
+
+    let mut level = Level {
+        id: description.id,
+        level_number,
+        start_location: description.start_location,
+        end_location: description.end_location,
+        x_functions,
+        y_functions,
+        gas_locations: description.gas_locations,
+        tick_time: description.tick_time,
+        require_conservative: description.require_conservative,
+        integrator: description.integrator,
+        solution: description.solution,
+        par: None,
+    };
+
+    if let Some(solution) = &level.solution { // a solution that can't be built is a mistake in the file
+        solution.to_gradient(&level).map_err(|error| vec![format!("invalid solution: {}", error)])?;
+    }
+
+    // a solution that no longer finishes (after a change to tick_time or the integrator, say) only loses the par, so
+    // the level can still be played. verify_levels and the level tests catch it
+    match score::par_score(&level) {
+        Ok(par) => level.par = par,
+        Err(error) => warn!("level {} ({}) has no par: {}", level_number + 1, level.id, error),
+    }
+
+    Ok(level)
+}
+
+/// Parse and validate the contents of a level file
+pub fn parse_level_file(bytes: &[u8]) -> Result<Vec<Level>, LevelLoadError> {
+    let level_file: LevelFile = ron::de::from_bytes(bytes).map_err(|error| LevelLoadError {
+        problems: vec![(None, error.to_string())],
+    })?;
+
+    if level_file.levels.is_empty() {
+        return Err(LevelLoadError { problems: vec![(None, String::from("level file contains no levels"))] })
+    }
+
+    let mut levels = Vec::new();
+    let mut problems = Vec::new();
+
+    let mut ids: Vec<String> = Vec::new();
+
+    for (index, description) in level_file.levels.into_iter().enumerate() {
+        if ids.contains(&description.id) { // saved progress is looked up by id, so ids must be unique
+            problems.push((Some(index), format!("id \"{}\" is already used by another level", description.id)));
+        }
+        ids.push(description.id.clone());
+
+        match validate_level(index as u32, description) {
+            Ok(level) => levels.push(level),
+            Err(level_problems) => problems.extend(level_problems.into_iter().map(|problem| (Some(index), problem))), // keep track of which level each problem is from
+        }
+    }
+
+    if !problems.is_empty() {
+        Err(LevelLoadError { problems })
+    } else {
+        Ok(levels)
+    }
+}
+
+#[derive(Default)]
+/// Asset loader for level files (*.levels.ron)
+pub struct LevelSetLoader;
+
+impl AssetLoader for LevelSetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let levels = parse_level_file(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(LevelSet { levels }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["levels.ron"]
+    }
+}
+
+/// Start loading the level file
+fn load_level_file(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(LevelSetHandle(asset_server.load(LEVEL_FILE_PATH)));
+}
+
+/// Copy levels into the game state whenever the level file finishes loading (or is reloaded)
+fn apply_loaded_levels(
+    mut asset_events: EventReader<AssetEvent<LevelSet>>,
+    level_sets: Res<Assets<LevelSet>>,
+    level_set_handle: Res<LevelSetHandle>,
+    save_data: Res<SaveData>,
+    mut game_state: Query<&mut GameState>,
+    mut loading_state: ResMut<State<LoadingState>>,
+) {
+    for event in asset_events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                if *handle != level_set_handle.0 { // only care about the level file used by the game
+                    continue;
+                }
+
+                let level_set = match level_sets.get(handle) {
+                    Some(level_set) => level_set,
+                    None => continue,
+                };
+
+                let mut game_state = game_state.single_mut();
+                game_state.level_info = level_set.levels.clone(); // replace level info with loaded levels
+
+                if let (AssetEvent::Created { .. }, Some(index)) = (event, save_data.current_level_index(&game_state.level_info)) {
+                    game_state.current_level = index as u32; // carry on from the saved level when the game starts
+                }
+
+                if game_state.current_level as usize >= game_state.level_info.len() { // level may no longer exist after a reload
+                    game_state.current_level = 0;
+                }
+
+                info!("loaded {} levels", game_state.level_info.len());
+
+                match loading_state.current() {
+                    LoadingState::Loading => {
+                        loading_state.set(LoadingState::Loaded).unwrap(); // levels are now ready to play
+                    },
+                    LoadingState::Loaded => {},
+                }
+            },
+            AssetEvent::Removed { .. } => {},
+        }
+    }
+}
+
+/// Plugin for loading levels from level files
+pub struct LevelLoaderPlugin;
+
+impl Plugin for LevelLoaderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<LevelSet>();
+        app.init_asset_loader::<LevelSetLoader>();
+        app.add_state(LoadingState::Loading); // no level data until the level file has loaded
+        app.add_startup_system(load_level_file);
+        app.add_system(apply_loaded_levels);
+    }
+}
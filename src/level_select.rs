@@ -0,0 +1,262 @@
+use bevy::{
+    prelude::*,
+    asset::AssetServer,
+};
+
+use crate::{GameState, Simulating};
+use crate::level::LoadLevel;
+use crate::save::SaveData;
+use crate::ui::ExpressionInput;
+
+use crate::constants::{
+    NORMAL_BUTTON_COLOR,
+    HOVERED_BUTTON_COLOR,
+    NORMAL_BUTTON_TEXT_COLOR,
+    BUTTON_WIDTH,
+    BUTTON_HEIGHT,
+    BUTTON_SPACING,
+    LEVEL_SELECT_BACKGROUND_COLOR,
+    LEVEL_SELECT_ROW_WIDTH,
+    LEVEL_SELECT_ROW_HEIGHT,
+    LOCKED_LEVEL_COLOR,
+    LOCKED_LEVEL_TEXT_COLOR,
+    COMPLETED_LEVEL_TEXT_COLOR,
+};
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+/// enum to store whether the level select is open
+pub enum MenuState {
+    Playing, // playing the current level
+    LevelSelect, // picking a level to play
+}
+
+#[derive(Component)]
+/// Struct to label the level select, covering the whole window
+pub struct LevelSelectMenu;
+
+#[derive(Component)]
+/// Struct for the button to play a level in the level select
+pub struct LevelSelectButton {
+    pub level: u32, // index of the level
+    pub unlocked: bool, // whether the level can be played
+}
+
+#[derive(Component)]
+/// Struct to indicate button that closes the level select without changing level
+pub struct LevelSelectBackButton;
+
+/// Text for a level's row in the level select: its number, status, best time and gas collected
+pub fn level_row_text(game_state: &GameState, save_data: &SaveData, index: usize) -> String {
+    let level = &game_state.level_info[index];
+    let progress = save_data.progress(level);
+
+    let status = if save_data.is_completed(level) {
+        "completed"
+    } else if save_data.is_unlocked(&game_state.level_info, index) {
+        "unlocked"
+    } else {
+        "locked"
+    };
+
+    let mut text = format!("Level {}: {}", index + 1, status);
+
+    if let Some(best_time) = progress.and_then(|progress| progress.stats.best_time) {
+        text.push_str(&format!("    best time {:.2} s", best_time));
+    }
+
+    if !level.gas_locations.is_empty() {
+        let most_gas = progress.map_or(0, |progress| progress.stats.most_gas);
+        text.push_str(&format!("    gas {}/{}", most_gas, level.gas_locations.len()));
+    }
+
+    text
+}
+
+/// Open and close the level select with L, ignored while typing in an expression input. Escape also closes it
+fn level_select_keyboard_system(
+    keys: Res<Input<KeyCode>>,
+    expression_inputs: Query<&ExpressionInput>,
+    mut menu_state: ResMut<State<MenuState>>,
+) {
+    if expression_inputs.iter().any(|expression_input| expression_input.focused) {
+        return
+    }
+
+    let open = *menu_state.current() == MenuState::LevelSelect;
+
+    if keys.just_pressed(KeyCode::L) || (open && keys.just_pressed(KeyCode::Escape)) {
+        let next_state = if open { MenuState::Playing } else { MenuState::LevelSelect };
+        menu_state.overwrite_set(next_state).unwrap(); // update state
+    }
+}
+
+/// Stop simulating and spawn the level select, listing every level with its progress
+fn level_select_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    game_state: Query<&GameState>,
+    save_data: Res<SaveData>,
+    mut simulating_state: ResMut<State<Simulating>>,
+) {
+    if *simulating_state.current() == Simulating::Simulating {
+        simulating_state.overwrite_set(Simulating::NotSimulating).unwrap(); // pause while picking a level
+    }
+
+    let game_state = game_state.single();
+
+    let style = TextStyle {
+        font: asset_server.load("fonts/tahoma.ttf"),
+        font_size: 20.0,
+        color: NORMAL_BUTTON_TEXT_COLOR,
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: LEVEL_SELECT_BACKGROUND_COLOR.into(),
+            z_index: ZIndex::Global(10), // above the rest of the UI
+            ..default()
+        })
+        .insert(Interaction::default()) // keeps the camera from panning when dragging on the menu
+        .insert(LevelSelectMenu)
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section("Levels", TextStyle { font_size: 40.0, ..style.clone() })
+                    .with_style(Style {
+                        margin: UiRect::all(Val::Px(BUTTON_SPACING)),
+                        ..default()
+                    })
+            );
+
+            for index in 0..game_state.level_info.len() {
+                let unlocked = save_data.is_unlocked(&game_state.level_info, index);
+                let text_color = if save_data.is_completed(&game_state.level_info[index]) {
+                    COMPLETED_LEVEL_TEXT_COLOR
+                } else if unlocked {
+                    NORMAL_BUTTON_TEXT_COLOR
+                } else {
+                    LOCKED_LEVEL_TEXT_COLOR
+                };
+
+                parent
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(LEVEL_SELECT_ROW_WIDTH), Val::Px(LEVEL_SELECT_ROW_HEIGHT)),
+                            margin: UiRect::all(Val::Px(BUTTON_SPACING / 4.)),
+                            padding: UiRect::horizontal(Val::Px(BUTTON_SPACING)),
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: if unlocked { NORMAL_BUTTON_COLOR } else { LOCKED_LEVEL_COLOR }.into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            level_row_text(game_state, &save_data, index),
+                            TextStyle { color: text_color, ..style.clone() },
+                        ));
+                    })
+                    .insert(LevelSelectButton { level: index as u32, unlocked });
+            }
+
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(BUTTON_WIDTH), Val::Px(BUTTON_HEIGHT)),
+                        margin: UiRect::all(Val::Px(BUTTON_SPACING)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: NORMAL_BUTTON_COLOR.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section("Back", style.clone()));
+                })
+                .insert(LevelSelectBackButton);
+        });
+}
+
+/// Remove the level select once it closes
+fn level_select_cleanup(mut commands: Commands, menu: Query<Entity, With<LevelSelectMenu>>) {
+    for entity in menu.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Load the clicked level, if it is unlocked, and close the level select
+fn level_select_button_system(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &LevelSelectButton),
+        Changed<Interaction>,
+    >,
+    mut load_level: EventWriter<LoadLevel>,
+    mut menu_state: ResMut<State<MenuState>>,
+) {
+    for (interaction, mut color, button) in &mut interaction_query {
+        if !button.unlocked { // locked levels can't be picked
+            continue;
+        }
+
+        match *interaction {
+            Interaction::Clicked => {
+                load_level.send(LoadLevel { level: button.level }); // field is cleared and buttons reset
+                menu_state.overwrite_set(MenuState::Playing).unwrap(); // close the level select
+            },
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON_COLOR.into(); // change color on hover 
+            },
+            Interaction::None => {
+                *color = NORMAL_BUTTON_COLOR.into(); // change color back on no hover/interaction   
+            },
+        }
+    }
+}
+
+/// Close the level select without changing level
+fn level_select_back_button_system(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<LevelSelectBackButton>),
+    >,
+    mut menu_state: ResMut<State<MenuState>>,
+) {
+    for (interaction, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                menu_state.overwrite_set(MenuState::Playing).unwrap(); // close the level select
+            },
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON_COLOR.into(); // change color on hover 
+            },
+            Interaction::None => {
+                *color = NORMAL_BUTTON_COLOR.into(); // change color back on no hover/interaction   
+            },
+        }
+    }
+}
+
+/// Plugin for the level select
+pub struct LevelSelectPlugin;
+
+impl Plugin for LevelSelectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state(MenuState::Playing); // start playing the saved level
+        app.add_system(level_select_keyboard_system);
+        app.add_system_set(SystemSet::on_enter(MenuState::LevelSelect).with_system(level_select_setup));
+        app.add_system_set(SystemSet::on_exit(MenuState::LevelSelect).with_system(level_select_cleanup));
+        app.add_system_set(
+            SystemSet::on_update(MenuState::LevelSelect)
+                .with_system(level_select_button_system)
+                .with_system(level_select_back_button_system)
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use std::f32::consts::PI;
+
+use bevy::{
+    prelude::*,
+    render::camera::ScalingMode,
+    asset::AssetServer
+};
+
+pub mod constants; 
+mod expression;
+mod derivative;
+mod gradient_field;
+mod ui;
+mod level;
+mod level_loader;
+mod field_analysis;
+mod integrator;
+mod simulation;
+mod preview;
+mod trail;
+mod field_render;
+mod heatmap;
+mod contour;
+mod camera;
+mod axes;
+mod probe;
+mod solution;
+mod save;
+mod level_select;
+mod score;
+pub mod verifier;
+pub mod solver;
+mod hint;
+mod history;
+
+//use constants::{TICK_TIME, VERTICAL_WINDOW_HEIGHT, BACKGROUND_COLOR, PLAYER_SCALE};
+use constants::{VERTICAL_WINDOW_HEIGHT, PLAYER_SCALE, FIXED_TIMESTEP, MAX_STEPS_PER_FRAME, SAVE_FILE_PATH};
+
+use expression::Expression;
+
+use gradient_field::{GradientArrowPlugin, Gradient, GradientOperation, GradientOperationState};
+
+use ui::{UiPlugin, NewLevelText};
+
+use level::{LevelPlugin};
+
+use level_loader::{LevelLoaderPlugin, LoadingState};
+
+use field_analysis::FieldAnalysisPlugin;
+
+use integrator::Integrator;
+
+use simulation::SimulationClock;
+
+use solution::FieldSolution;
+use score::{Score, ScorePlugin};
+
+use preview::PreviewPlugin;
+use trail::TrailPlugin;
+
+use field_render::FieldRenderPlugin;
+
+use heatmap::HeatmapPlugin;
+
+use contour::ContourPlugin;
+
+use camera::{CameraPlugin, MainCamera};
+
+use axes::AxesPlugin;
+use probe::ProbePlugin;
+
+use save::SavePlugin;
+
+use level_select::LevelSelectPlugin;
+
+use hint::HintPlugin;
+use history::HistoryPlugin;
+
+#[derive(Clone, Debug)]
+/// struct to store level information 
+pub struct Level {
+    pub id: String, // name of the level that stays the same when levels are added or reordered
+    pub level_number: u32, // level number
+    pub start_location: (f32, f32), // starting location
+    pub end_location: (f32, f32), // ending location
+    pub x_functions: Vec<(String, Expression)>, // functions available for x dimension (String representation of function, function parsed from that string)
+    pub y_functions: Vec<(String, Expression)>, // functions available for y dimension (String representation of function, function parsed from that string)
+    pub gas_locations: Vec<(f32, f32)>, // locations of gas stops
+    pub tick_time: f32,
+    pub require_conservative: bool, // whether the level can only be finished with a conservative field
+    pub integrator: Integrator, // numerical method for moving the player through the field
+    pub solution: Option<FieldSolution>, // intended solution, if the level has one
+    pub par: Option<Score>, // score of the intended solution
+}
+
+#[derive(Component, Clone, Debug)]
+/// Game state. Stores relevant information about the game
+pub struct GameState {
+    pub level_info: Vec<Level>, // essentially a constant that includes all information about levels for the game
+    pub current_level: u32, // current level number
+    pub gas_collected: Vec<u32>, // number of gas stops collected as a one-hot encoded vector
+    pub hints_shown: u32, // number of hints revealed for the current level
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq,)]
+/// enum to store information on simulating or not
+pub enum Simulating {
+    Simulating,
+    NotSimulating,
+}
+
+#[derive(Component)]
+pub struct Player {
+    pub x: f32, // x position of player
+    pub y: f32, // y position of player
+    pub previous_x: f32, // x position of player before the last fixed step
+    pub previous_y: f32, // y position of player before the last fixed step
+}
+
+impl GameState {
+    /// Initialize new game state. Levels are added once they are loaded from the level file
+    pub fn new() -> Self {
+        GameState {
+            level_info: Vec::new(), // filled in once the level file has loaded
+            current_level: 0,
+            gas_collected: vec![0],
+            hints_shown: 0,
+        }
+    }
+}
+
+/// Plugin for the whole game. Needs DefaultPlugins, or the plugins they include, to be added first
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup);
+        app.add_startup_system(spawn_player);
+        app.add_startup_system(initialize_gamestate);
+        app.add_state(Simulating::NotSimulating); // set initial simulating state
+        app.insert_resource(SimulationClock::new());
+        app.add_system_set(SystemSet::on_update(LoadingState::Loaded).with_system(player_movement)); // player needs level info to move
+        app.add_plugin(LevelLoaderPlugin);
+        app.add_plugin(CameraPlugin);
+        app.add_plugin(GradientArrowPlugin);
+        app.add_plugin(FieldRenderPlugin);
+        app.add_plugin(HeatmapPlugin);
+        app.add_plugin(ContourPlugin);
+        app.add_plugin(AxesPlugin);
+        app.add_plugin(ProbePlugin);
+        app.add_plugin(FieldAnalysisPlugin);
+        app.add_plugin(PreviewPlugin);
+        app.add_plugin(TrailPlugin);
+        app.add_plugin(UiPlugin);
+        app.add_plugin(LevelPlugin);
+        app.add_plugin(SavePlugin);
+        app.add_plugin(LevelSelectPlugin);
+        app.add_plugin(ScorePlugin);
+        app.add_plugin(HintPlugin);
+        app.add_plugin(HistoryPlugin);
+    }
+}
+
+/// Setup the game
+fn setup(mut commands: Commands) {
+    let mut camera_bundle = Camera2dBundle::default();
+
+    camera_bundle.projection.scaling_mode = ScalingMode::FixedVertical(VERTICAL_WINDOW_HEIGHT);
+    commands.spawn(camera_bundle).insert(MainCamera);
+}
+
+/// Create player 
+fn spawn_player(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(SpriteBundle {
+            texture: asset_server.load("player.png"),        
+            transform: Transform::from_xyz(5., 0., 1.) // set initial position to (0,0)
+                    .with_scale(Vec3::new(PLAYER_SCALE, PLAYER_SCALE, 1.)) // with no scaling 
+                    .with_rotation(Quat::from_rotation_z(0.)), // with no rotation
+                ..default()
+            
+        })
+        .insert(Player {x: 0., y: 0., previous_x: 0., previous_y: 0.}); // insert player component
+        
+}
+
+
+/// move player. The simulation runs on a fixed timestep so a field always gives the same path, whatever the frame rate.
+/// The drawn player is interpolated between the last two steps
+fn player_movement(
+    mut player: Query<(&mut Player, &mut Transform)>, 
+    gradient: Query<&Gradient>, 
+    simulating_state: Res<State<Simulating>>, 
+    mut game_state: Query<&mut GameState>,
+    mut clock: ResMut<SimulationClock>,
+    time: Res<Time>,
+) {
+    let gradient = gradient.single(); // should be exclusively 1 gradient
+    let mut game_state = game_state.single_mut(); // should be exclusively 1 game state
+    let game_state = &mut *game_state; // borrow level info and gas collected separately
+    let level = &game_state.level_info[game_state.current_level as usize];
+ 
+    match simulating_state.current() {
+        Simulating::Simulating => { // move player on if currently simulating 
+            if game_state.gas_collected.len() != level.gas_locations.len() { // gas from the last level may still be around
+                game_state.gas_collected = vec![0; level.gas_locations.len()];
+            }
+
+            clock.accumulator += time.delta_seconds();
+
+            for (mut player_struct, mut transform) in player.iter_mut() {
+                let mut steps_this_frame = 0;
+
+                while clock.accumulator >= FIXED_TIMESTEP && steps_this_frame < MAX_STEPS_PER_FRAME {
+                    clock.accumulator -= FIXED_TIMESTEP;
+                    clock.steps += 1;
+                    steps_this_frame += 1;
+
+                    player_struct.previous_x = player_struct.x;
+                    player_struct.previous_y = player_struct.y;
+
+                    let mut position = (player_struct.x, player_struct.y);
+                    let reached_end = simulation::step(level, gradient, &mut position, &mut game_state.gas_collected);
+                    clock.distance += simulation::distance((player_struct.x, player_struct.y), position);
+
+                    // update player struct coords
+                    player_struct.x = position.0;
+                    player_struct.y = position.1;
+
+                    if reached_end { // stay at the end so the level can be finished
+                        player_struct.previous_x = player_struct.x;
+                        player_struct.previous_y = player_struct.y;
+                        clock.accumulator = 0.;
+                        break;
+                    }
+                }
+
+                if steps_this_frame == MAX_STEPS_PER_FRAME { // running too slowly to keep up, so drop the time that's left
+                    clock.accumulator = clock.accumulator.min(FIXED_TIMESTEP);
+                }
+
+                // draw player between the last two steps
+                let alpha = clock.alpha();
+                transform.translation.x = player_struct.previous_x + (player_struct.x - player_struct.previous_x) * alpha;
+                transform.translation.y = player_struct.previous_y + (player_struct.y - player_struct.previous_y) * alpha;
+
+                // update player angle 
+                let angle = gradient.y(player_struct.x, player_struct.y).atan2(gradient.x(player_struct.x, player_struct.y)) - PI/2.;
+                transform.rotation = Quat::from_rotation_z(angle);
+            }
+        }, 
+        Simulating::NotSimulating => { // set player to start location when not simulating
+            clock.reset(); // next run starts from the beginning
+
+            for (mut player_struct, mut transform) in player.iter_mut() {
+                // update player struct coords 
+                player_struct.x = level.start_location.0;
+                player_struct.y = level.start_location.1;
+                player_struct.previous_x = player_struct.x;
+                player_struct.previous_y = player_struct.y;
+
+                transform.translation.x = player_struct.x;
+                transform.translation.y = player_struct.y;
+            }
+        },
+    } 
+}
+
+/// Setup game state, and read saved progress. The saved current level is restored once levels load
+fn initialize_gamestate(mut commands: Commands) {
+    commands
+        .spawn(GameState::new()); // spawn game state
+
+    commands.insert_resource(save::load_save(SAVE_FILE_PATH));
+}
\ No newline at end of file
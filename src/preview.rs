@@ -0,0 +1,204 @@
+use bevy::prelude::*;
+
+use crate::{Gradient, GameState, Level, Simulating, LoadingState};
+use crate::simulation::{self, distance};
+
+use crate::constants::{
+    PREVIEW_STEPS,
+    PREVIEW_DOT_COUNT,
+    PREVIEW_DOT_SIZE,
+    PREVIEW_DOT_COLOR,
+    PREVIEW_MARKER_SIZE,
+    PREVIEW_GAS_MARKER_COLOR,
+    PREVIEW_END_MARKER_COLOR,
+    PREVIEW_MISSED_END_MARKER_COLOR,
+    ENDING_LOCATION_ERROR,
+    MAX_GAS_CANS,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+/// Path the player will take through the current field, found by simulating ahead from the start of the level
+pub struct PredictedPath {
+    pub points: Vec<(f32, f32)>, // position after each fixed step
+    pub gas_hits: Vec<(f32, f32)>, // positions where gas cans are collected, in the order they are collected
+    pub end_hit: Option<(f32, f32)>, // position where the path first passes the portal, or where it finishes the level
+    pub finished: bool, // whether the path finishes the level at end_hit, rather than passing with gas still missing
+}
+
+/// Simulate a level from its start location for up to the given number of fixed steps, stopping early if the end is reached.
+/// Uses the same steps as the real simulation, so the prediction matches what happens when simulating. Passing the portal
+/// before every gas can is collected is recorded too, so the player can see how close the path came
+pub fn predict_path(level: &Level, gradient: &Gradient, steps: u32) -> PredictedPath {
+    let mut position = level.start_location;
+    let mut gas_collected = vec![0; level.gas_locations.len()];
+
+    let mut path = PredictedPath {
+        points: vec![position],
+        gas_hits: Vec::new(),
+        end_hit: None,
+        finished: false,
+    };
+
+    for _ in 0..steps {
+        let collected_before = gas_collected.iter().sum::<u32>();
+        let reached_end = simulation::step(level, gradient, &mut position, &mut gas_collected);
+
+        path.points.push(position);
+
+        for _ in collected_before..gas_collected.iter().sum::<u32>() { // mark each gas can collected this step
+            path.gas_hits.push(position);
+        }
+
+        if reached_end {
+            path.end_hit = Some(position);
+            path.finished = true;
+            break;
+        }
+
+        if path.end_hit.is_none() && distance(level.end_location, position) < ENDING_LOCATION_ERROR { // passed with gas missing
+            path.end_hit = Some(position);
+        }
+    }
+
+    path
+}
+
+#[derive(Resource)]
+/// Predicted path for the current field and level
+pub struct PathPreview {
+    pub path: Option<PredictedPath>, // None until levels have loaded
+    pub level: u32, // level the path was predicted for
+}
+
+#[derive(Component)]
+/// Struct to label a dot of the predicted path
+pub struct PreviewDot {
+    pub index: u32, // which dot along the path this is
+}
+
+#[derive(Component)]
+/// Struct to label a marker where the predicted path collects a gas can or passes the portal
+pub struct PreviewMarker {
+    pub index: u32, // gas can markers come first, the portal marker is last
+}
+
+/// Spawn the dots and markers for the predicted path. They start invisible
+fn preview_setup(mut commands: Commands) {
+    for index in 0..PREVIEW_DOT_COUNT {
+        commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: PREVIEW_DOT_COLOR,
+                    custom_size: Some(Vec2::splat(PREVIEW_DOT_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(0., 0., 0.5), // above arrows, below player
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(PreviewDot { index });
+    }
+
+    for index in 0..=MAX_GAS_CANS { // one marker per gas can and one for the portal
+        commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: if index == MAX_GAS_CANS { PREVIEW_END_MARKER_COLOR } else { PREVIEW_GAS_MARKER_COLOR },
+                    custom_size: Some(Vec2::splat(PREVIEW_MARKER_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(0., 0., 0.6).with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4)), // diamond
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(PreviewMarker { index });
+    }
+}
+
+/// Predict the path again whenever the field or the level changes
+fn preview_update(
+    gradient: Query<(&Gradient, ChangeTrackers<Gradient>)>,
+    game_state: Query<&GameState>,
+    mut preview: ResMut<PathPreview>,
+) {
+    let (gradient, gradient_tracker) = gradient.single(); // should only be 1 gradient
+    let game_state = game_state.single();
+
+    if !gradient_tracker.is_changed() && preview.path.is_some() && preview.level == game_state.current_level {
+        return
+    }
+
+    preview.path = Some(predict_path(&game_state.level_info[game_state.current_level as usize], gradient, PREVIEW_STEPS));
+    preview.level = game_state.current_level;
+}
+
+/// Place the dots and markers along the predicted path. Hidden while simulating, since the player shows the real path
+fn preview_display_system(
+    preview: Res<PathPreview>,
+    simulating_state: Res<State<Simulating>>,
+    mut dots: Query<(&PreviewDot, &mut Transform, &mut Visibility), Without<PreviewMarker>>,
+    mut markers: Query<(&PreviewMarker, &mut Transform, &mut Visibility, &mut Sprite), Without<PreviewDot>>,
+) {
+    let path = match (&preview.path, simulating_state.current()) {
+        (Some(path), Simulating::NotSimulating) => path,
+        _ => {
+            for (_, _, mut visibility) in dots.iter_mut() {
+                visibility.is_visible = false;
+            }
+            for (_, _, mut visibility, _) in markers.iter_mut() {
+                visibility.is_visible = false;
+            }
+            return
+        },
+    };
+
+    let stride = (PREVIEW_STEPS / PREVIEW_DOT_COUNT).max(1) as usize; // steps between dots
+
+    for (dot, mut transform, mut visibility) in dots.iter_mut() {
+        match path.points.get((dot.index as usize + 1) * stride) {
+            Some(point) => {
+                transform.translation.x = point.0;
+                transform.translation.y = point.1;
+                visibility.is_visible = true;
+            },
+            None => visibility.is_visible = false, // path ended early
+        }
+    }
+
+    for (marker, mut transform, mut visibility, mut sprite) in markers.iter_mut() {
+        let point = if marker.index == MAX_GAS_CANS {
+            path.end_hit
+        } else {
+            path.gas_hits.get(marker.index as usize).copied()
+        };
+
+        match point {
+            Some(point) => {
+                transform.translation.x = point.0;
+                transform.translation.y = point.1;
+                visibility.is_visible = true;
+            },
+            None => visibility.is_visible = false,
+        }
+
+        let color = if path.finished { PREVIEW_END_MARKER_COLOR } else { PREVIEW_MISSED_END_MARKER_COLOR };
+        if marker.index == MAX_GAS_CANS && sprite.color != color { // portal marker shows whether the level is finished there
+            sprite.color = color;
+        }
+    }
+}
+
+/// Plugin for drawing the path the player will take before simulating
+pub struct PreviewPlugin;
+
+impl Plugin for PreviewPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PathPreview { path: None, level: 0 });
+        app.add_startup_system(preview_setup);
+        app.add_system_set(
+            SystemSet::on_update(LoadingState::Loaded) // prediction needs level info
+                .with_system(preview_update)
+                .with_system(preview_display_system.after(preview_update))
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use bevy::prelude::*;
+
+use crate::Gradient;
+use crate::camera::VisibleRect;
+use crate::ui::ExpressionInput;
+
+use crate::constants::{
+    FIELD_SCALE,
+    TICK_LABEL_COLOR,
+    PROBE_FONT_SIZE,
+    PROBE_OFFSET,
+};
+
+#[derive(Resource)]
+/// Whether the probe tooltip is shown next to the cursor
+pub struct ProbeSettings {
+    pub visible: bool, // toggled with P
+}
+
+#[derive(Component)]
+/// Struct to label the tooltip showing each function of the field at the cursor
+pub struct ProbeTooltip;
+
+/// Text listing each function in one direction with its value, followed by the combined value
+pub fn probe_text(direction: &str, terms: &[(String, f32)], combined: f32) -> String {
+    let mut text = format!("{} direction", direction);
+
+    for (function, value) in terms {
+        text.push_str(&format!("\n  {} = {:.3}", function, value));
+    }
+    text.push_str(&format!("\n  combined = {:.3}", combined));
+
+    text
+}
+
+/// Spawn the probe tooltip. It starts invisible
+fn probe_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font: asset_server.load("fonts/tahoma.ttf"),
+                font_size: PROBE_FONT_SIZE,
+                color: TICK_LABEL_COLOR,
+            }).with_alignment(TextAlignment::TOP_RIGHT), // left of the cursor, so it doesn't cover the cursor readout
+            transform: Transform::from_xyz(0., 0., 2.), // above everything, including the player
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(ProbeTooltip);
+}
+
+/// Toggle the probe with P, ignored while typing in an expression input
+fn probe_toggle_system(
+    keys: Res<Input<KeyCode>>,
+    expression_inputs: Query<&ExpressionInput>,
+    mut settings: ResMut<ProbeSettings>,
+) {
+    if keys.just_pressed(KeyCode::P) && !expression_inputs.iter().any(|expression_input| expression_input.focused) {
+        settings.visible = !settings.visible;
+    }
+}
+
+/// Show each function of the field evaluated at the cursor, and what they combine to, next to the cursor. The cursor
+/// is converted to world coordinates through the visible rectangle, the same way the arrows are placed
+fn probe_tooltip_system(
+    settings: Res<ProbeSettings>,
+    rect: Res<VisibleRect>,
+    windows: Res<Windows>,
+    gradient: Query<&Gradient>,
+    mut tooltip: Query<(&mut Text, &mut Transform, &mut Visibility), With<ProbeTooltip>>,
+) {
+    let (mut text, mut transform, mut visibility) = tooltip.single_mut();
+
+    let (window, cursor) = match windows.get_primary().and_then(|window| window.cursor_position().map(|cursor| (window, cursor))) {
+        Some(window_and_cursor) if settings.visible => window_and_cursor,
+        _ => { // hidden, or cursor isn't over the window
+            visibility.is_visible = false;
+            return
+        },
+    };
+
+    let gradient = gradient.single(); // should only be 1 gradient
+    let world_per_pixel = rect.height() / window.height();
+
+    let (x, y) = rect.window_to_world(window, cursor);
+
+    text.sections[0].value = format!(
+        "probe at ({:.2}, {:.2})\n{}\n{}",
+        x, y,
+        probe_text("x", &gradient.x_terms(x, y), gradient.x(x, y) / FIELD_SCALE), // combined field as the player wrote it
+        probe_text("y", &gradient.y_terms(x, y), gradient.y(x, y) / FIELD_SCALE),
+    );
+
+    transform.translation.x = x - PROBE_OFFSET * world_per_pixel;
+    transform.translation.y = y - PROBE_OFFSET * world_per_pixel;
+    transform.scale = Vec3::splat(world_per_pixel);
+
+    visibility.is_visible = true;
+}
+
+/// Plugin for the probe tooltip
+pub struct ProbePlugin;
+
+impl Plugin for ProbePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ProbeSettings { visible: false });
+        app.add_startup_system(probe_setup);
+        app.add_system(probe_toggle_system);
+        app.add_system(probe_tooltip_system.after(probe_toggle_system));
+    }
+}
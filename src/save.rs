@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Serialize, Deserialize};
+
+use crate::{GameState, Level, Simulating, LoadingState};
+use crate::level::{LevelCompleted, level_update_system};
+use crate::solution::FieldSolution;
+
+use crate::constants::{SAVE_FILE_PATH, SAVE_VERSION};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)] // stats added in later versions start empty
+/// Stats for one level, across every session
+pub struct LevelStats {
+    pub attempts: u32, // number of times the level was simulated
+    pub completions: u32, // number of times the level was finished
+    pub best_time: Option<f32>, // fewest seconds of simulation taken to finish the level
+    pub most_gas: u32, // most gas cans collected in one run
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+/// Saved progress for one level
+pub struct LevelProgress {
+    pub unlocked: bool, // whether the level can be played. The first level is always unlocked
+    pub completed: bool, // whether the level has been finished
+    pub best_solution: Option<FieldSolution>, // field that finished the level in the best time
+    pub stats: LevelStats, // stats for the level
+}
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// Player progress, saved between sessions. Levels are looked up by id rather than position, so adding or
+/// reordering levels keeps progress on the levels that were already there
+pub struct SaveData {
+    pub version: u32, // SAVE_VERSION of the game that wrote the save
+    #[serde(default)]
+    pub current_level: Option<String>, // id of the level being played
+    #[serde(default)]
+    pub levels: BTreeMap<String, LevelProgress>, // progress for each level played, by level id
+}
+
+impl SaveData {
+    /// New method. Starts with no progress
+    pub fn new() -> Self {
+        Self {
+            version: SAVE_VERSION,
+            current_level: None,
+            levels: BTreeMap::new(),
+        }
+    }
+
+    /// Progress for a level, if it has been played
+    pub fn progress(&self, level: &Level) -> Option<&LevelProgress> {
+        self.levels.get(&level.id)
+    }
+
+    /// Progress for a level, started empty if it hasn't been played
+    pub fn progress_mut(&mut self, level: &Level) -> &mut LevelProgress {
+        self.levels.entry(level.id.clone()).or_default()
+    }
+
+    /// Whether the level at an index can be played
+    pub fn is_unlocked(&self, levels: &[Level], index: usize) -> bool {
+        index == 0 || levels.get(index).and_then(|level| self.progress(level)).is_some_and(|progress| progress.unlocked)
+    }
+
+    /// Whether a level has been finished
+    pub fn is_completed(&self, level: &Level) -> bool {
+        self.progress(level).is_some_and(|progress| progress.completed)
+    }
+
+    /// Index of the level being played, if it is still in the levels and unlocked
+    pub fn current_level_index(&self, levels: &[Level]) -> Option<usize> {
+        let id = self.current_level.as_ref()?;
+        levels.iter()
+            .position(|level| level.id == *id)
+            .filter(|index| self.is_unlocked(levels, *index))
+    }
+
+    /// Record finishing the level at an index, which unlocks the level after it
+    pub fn record_completion(&mut self, levels: &[Level], index: usize, time: f32, field: &FieldSolution) {
+        let progress = self.progress_mut(&levels[index]);
+
+        progress.completed = true;
+        progress.stats.completions += 1;
+        progress.stats.most_gas = levels[index].gas_locations.len() as u32; // every gas can is needed to finish
+
+        if progress.stats.best_time.is_none_or(|best_time| time < best_time) {
+            progress.stats.best_time = Some(time);
+            progress.best_solution = Some(field.clone());
+        }
+
+        if let Some(next_level) = levels.get(index + 1) {
+            self.progress_mut(next_level).unlocked = true;
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Error for a save file that could not be read or written
+pub enum SaveError {
+    Io(io::Error), // file couldn't be read or written
+    Format(String), // file isn't a valid save
+    NewerVersion(u32), // file was written by a newer version of the game, with the version it was written with
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "{}", error),
+            SaveError::Format(error) => write!(f, "invalid save file: {}", error),
+            SaveError::NewerVersion(version) => write!(f, "save file is from a newer version of the game (version {}, expected at most {})", version, SAVE_VERSION),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+/// Parse the contents of a save file. Saves from older versions load with anything added since left empty
+pub fn parse_save(text: &str) -> Result<SaveData, SaveError> {
+    let mut save_data: SaveData = ron::from_str(text).map_err(|error| SaveError::Format(error.to_string()))?;
+
+    if save_data.version > SAVE_VERSION {
+        return Err(SaveError::NewerVersion(save_data.version))
+    }
+
+    save_data.version = SAVE_VERSION; // written back in the current format
+    Ok(save_data)
+}
+
+/// Read the save file. Starts with no progress if there is no save yet, or it can't be read
+pub fn load_save(path: &str) -> SaveData {
+    let result = fs::read_to_string(path)
+        .map_err(SaveError::Io)
+        .and_then(|text| parse_save(&text));
+
+    match result {
+        Ok(save_data) => save_data,
+        Err(SaveError::Io(error)) if error.kind() == io::ErrorKind::NotFound => SaveData::new(), // first time playing
+        Err(error) => {
+            warn!("could not load progress from {}: {}", path, error);
+            SaveData::new()
+        },
+    }
+}
+
+/// Write progress to the save file
+pub fn write_save(path: &str, save_data: &SaveData) -> Result<(), SaveError> {
+    let text = ron::ser::to_string_pretty(save_data, PrettyConfig::default()).map_err(|error| SaveError::Format(error.to_string()))?;
+    fs::write(path, text).map_err(SaveError::Io)
+}
+
+/// Remember which level is being played, so the game starts there next time
+fn current_level_save_system(
+    game_state: Query<&GameState>,
+    mut save_data: ResMut<SaveData>,
+) {
+    let game_state = game_state.single();
+    let id = &game_state.level_info[game_state.current_level as usize].id;
+
+    if save_data.current_level.as_ref() != Some(id) { // only mark as changed when it actually changes
+        save_data.current_level = Some(id.clone());
+    }
+}
+
+/// Count every run of the field towards the level's attempts
+fn attempt_save_system(
+    game_state: Query<&GameState>,
+    mut save_data: ResMut<SaveData>,
+) {
+    let game_state = game_state.single();
+
+    if let Some(level) = game_state.level_info.get(game_state.current_level as usize) { // simulating can start before levels load
+        save_data.progress_mut(level).stats.attempts += 1;
+    }
+}
+
+/// Keep track of the most gas collected in one run of each level
+fn gas_save_system(
+    game_state: Query<&GameState>,
+    mut save_data: ResMut<SaveData>,
+) {
+    let game_state = game_state.single();
+    let level = match game_state.level_info.get(game_state.current_level as usize) {
+        Some(level) => level,
+        None => return, // simulating can start before levels load
+    };
+
+    if game_state.gas_collected.len() != level.gas_locations.len() { // gas from the last level may still be around
+        return
+    }
+
+    let gas = game_state.gas_collected.iter().sum::<u32>();
+    if save_data.progress(level).map_or(0, |progress| progress.stats.most_gas) < gas {
+        save_data.progress_mut(level).stats.most_gas = gas;
+    }
+}
+
+/// Record finished levels
+fn completion_save_system(
+    mut level_completed: EventReader<LevelCompleted>,
+    game_state: Query<&GameState>,
+    mut save_data: ResMut<SaveData>,
+) {
+    let game_state = game_state.single();
+
+    for completed in level_completed.iter() {
+        save_data.record_completion(&game_state.level_info, completed.level as usize, completed.score.time, &completed.field);
+    }
+}
+
+/// Write the save file whenever progress changes
+fn write_save_system(save_data: Res<SaveData>) {
+    if !save_data.is_changed() || save_data.is_added() { // nothing new since it was loaded
+        return
+    }
+
+    if let Err(error) = write_save(SAVE_FILE_PATH, &save_data) {
+        warn!("could not save progress to {}: {}", SAVE_FILE_PATH, error);
+    }
+}
+
+/// Plugin for saving player progress. The save is read when the game state is set up
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(LoadingState::Loaded) // these systems need level info
+                .with_system(current_level_save_system)
+                .with_system(completion_save_system)
+        );
+        app.add_system_set(
+            SystemSet::on_update(Simulating::Simulating)
+                .with_system(gas_save_system.before(level_update_system)) // level may change once it's finished
+        );
+        app.add_system_set(SystemSet::on_enter(Simulating::Simulating).with_system(attempt_save_system));
+        app.add_system_to_stage(CoreStage::PostUpdate, write_save_system);
+    }
+}
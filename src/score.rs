@@ -0,0 +1,248 @@
+use bevy::{
+    prelude::*,
+    asset::AssetServer,
+    ui::FocusPolicy,
+};
+use serde::{Serialize, Deserialize};
+
+use crate::{Gradient, Level, GameState, Simulating};
+use crate::level::LevelCompleted;
+use crate::simulation;
+
+use crate::constants::{
+    VERIFY_MAX_STEPS,
+    PAR_TOLERANCE,
+    NORMAL_BUTTON_COLOR,
+    HOVERED_BUTTON_COLOR,
+    NORMAL_BUTTON_TEXT_COLOR,
+    BUTTON_WIDTH,
+    BUTTON_HEIGHT,
+    BUTTON_SPACING,
+    SUMMARY_BACKGROUND_COLOR,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+/// How well a level was finished. Smaller is better for everything
+pub struct Score {
+    pub time: f32, // seconds of simulation taken to reach the end
+    pub distance: f32, // length in world units of the path to the end
+    pub functions: u32, // number of functions used in the field
+    pub operations: u32, // number of operations combining them
+    #[serde(default)]
+    pub hints: u32, // number of hints revealed before finishing
+}
+
+impl Score {
+    /// Score for finishing a level with a field, in a run that took some time and distance, after revealing some hints
+    pub fn new(gradient: &Gradient, time: f32, distance: f32, hints: u32) -> Self {
+        Self {
+            time,
+            distance,
+            functions: gradient.function_count(),
+            operations: gradient.operation_count(),
+            hints,
+        }
+    }
+
+    /// Whether each part of the score is at or under par, in the order time, distance, functions, operations
+    pub fn at_par(&self, par: &Score) -> [bool; 4] {
+        [
+            at_par(self.time, par.time),
+            at_par(self.distance, par.distance),
+            self.functions <= par.functions,
+            self.operations <= par.operations,
+        ]
+    }
+}
+
+/// Whether a time or distance is no worse than par, allowing for rounding
+fn at_par(value: f32, par: f32) -> bool {
+    value <= par * (1. + PAR_TOLERANCE) + f32::EPSILON
+}
+
+/// Par for a level: the score of its intended solution. None if the level has no solution. Fails if the solution
+/// can't be built or doesn't finish the level within VERIFY_MAX_STEPS
+pub fn par_score(level: &Level) -> Result<Option<Score>, String> {
+    let solution = match &level.solution {
+        Some(solution) => solution,
+        None => return Ok(None),
+    };
+
+    let gradient = solution.to_gradient(level)?;
+    let run = simulation::run(level, &gradient, VERIFY_MAX_STEPS);
+
+    if !run.field_allowed {
+        return Err(String::from("solution isn't a conservative field, which the level needs"))
+    }
+
+    if !run.finished {
+        return Err(format!(
+            "solution doesn't finish the level, it ends at ({:.2}, {:.2}) with {}/{} gas collected",
+            run.position.0, run.position.1, run.gas_collected.iter().sum::<u32>(), level.gas_locations.len(),
+        ))
+    }
+
+    Ok(Some(Score::new(&gradient, run.time(), run.distance, 0)))
+}
+
+/// Text of the summary for completing the level at an index, comparing the score with par
+pub fn summary_text(level: u32, score: &Score, par: Option<&Score>) -> String {
+    let mut text = format!("Level {} complete!\n", level + 1);
+
+    let values = [
+        ("Time", format!("{:.2} s", score.time), par.map(|par| format!("{:.2} s", par.time))),
+        ("Path length", format!("{:.1}", score.distance), par.map(|par| format!("{:.1}", par.distance))),
+        ("Functions", score.functions.to_string(), par.map(|par| par.functions.to_string())),
+        ("Operations", score.operations.to_string(), par.map(|par| par.operations.to_string())),
+    ];
+
+    for (label, value, par_value) in values.iter() {
+        match par_value {
+            Some(par_value) => text.push_str(&format!("\n{}: {} (par {})", label, value, par_value)),
+            None => text.push_str(&format!("\n{}: {}", label, value)),
+        }
+    }
+    text.push_str(&format!("\nHints used: {}", score.hints)); // par never uses hints, so there's nothing to compare with
+
+    if let Some(par) = par {
+        let at_par = score.at_par(par).iter().filter(|at_par| **at_par).count();
+        text.push_str(&match at_par {
+            4 => String::from("\n\nAt or under par on everything!"),
+            _ => format!("\n\nAt or under par on {} of 4", at_par),
+        });
+    }
+
+    text
+}
+
+#[derive(Component)]
+/// Struct to label the level complete summary
+pub struct LevelSummary;
+
+#[derive(Component)]
+/// Struct to label the text of the level complete summary
+pub struct LevelSummaryText;
+
+#[derive(Component)]
+/// Struct to indicate button that closes the level complete summary
+pub struct SummaryContinueButton;
+
+/// Spawn the level complete summary. It starts hidden
+fn level_summary_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let style = TextStyle {
+        font: asset_server.load("fonts/tahoma.ttf"),
+        font_size: 20.0,
+        color: NORMAL_BUTTON_TEXT_COLOR,
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                display: Display::None,
+                ..default()
+            },
+            focus_policy: FocusPolicy::Pass, // the game can still be played around the summary
+            z_index: ZIndex::Global(5), // above the rest of the UI, below the level select
+            ..default()
+        })
+        .insert(LevelSummary)
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(2. * BUTTON_SPACING)),
+                        ..default()
+                    },
+                    background_color: SUMMARY_BACKGROUND_COLOR.into(),
+                    ..default()
+                })
+                .insert(Interaction::default()) // keeps the camera from panning when dragging on the summary
+                .with_children(|parent| {
+                    parent
+                        .spawn(TextBundle::from_section("", style.clone()))
+                        .insert(LevelSummaryText);
+
+                    parent
+                        .spawn(ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(BUTTON_WIDTH), Val::Px(BUTTON_HEIGHT)),
+                                margin: UiRect::top(Val::Px(2. * BUTTON_SPACING)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: NORMAL_BUTTON_COLOR.into(),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section("Continue", style));
+                        })
+                        .insert(SummaryContinueButton);
+                });
+        });
+}
+
+/// Show the summary when a level is finished
+fn level_summary_system(
+    mut level_completed: EventReader<LevelCompleted>,
+    game_state: Query<&GameState>,
+    mut summary: Query<&mut Style, With<LevelSummary>>,
+    mut text: Query<&mut Text, With<LevelSummaryText>>,
+) {
+    let completed = match level_completed.iter().last() {
+        Some(completed) => completed,
+        None => return,
+    };
+
+    let game_state = game_state.single();
+    let par = game_state.level_info.get(completed.level as usize).and_then(|level| level.par.as_ref());
+
+    text.single_mut().sections[0].value = summary_text(completed.level, &completed.score, par);
+    summary.single_mut().display = Display::Flex;
+}
+
+/// Hide the summary with its continue button
+fn summary_continue_button_system(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<SummaryContinueButton>),
+    >,
+    mut summary: Query<&mut Style, With<LevelSummary>>,
+) {
+    for (interaction, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                summary.single_mut().display = Display::None; // close the summary
+            },
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON_COLOR.into(); // change color on hover 
+            },
+            Interaction::None => {
+                *color = NORMAL_BUTTON_COLOR.into(); // change color back on no hover/interaction   
+            },
+        }
+    }
+}
+
+/// Hide the summary once the next run starts
+fn summary_hide_system(mut summary: Query<&mut Style, With<LevelSummary>>) {
+    summary.single_mut().display = Display::None;
+}
+
+/// Plugin for the level complete summary
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(level_summary_setup);
+        app.add_system(level_summary_system);
+        app.add_system(summary_continue_button_system);
+        app.add_system_set(SystemSet::on_enter(Simulating::Simulating).with_system(summary_hide_system));
+    }
+}
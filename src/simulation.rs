@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+
+use crate::{Gradient, Level};
+use crate::field_analysis::analyze_field;
+
+use crate::constants::{FIXED_TIMESTEP, MOVEMENT_SCALE_PER_SECOND, ENDING_LOCATION_ERROR};
+
+#[derive(Resource)]
+/// Clock for running the simulation on a fixed timestep, independent of the frame rate
+pub struct SimulationClock {
+    pub accumulator: f32, // time in seconds that has passed but not been simulated yet
+    pub steps: u64, // number of fixed steps simulated in the current run
+    pub distance: f32, // distance in world units the player has moved in the current run
+}
+
+impl SimulationClock {
+    /// New method. Starts with no time to simulate
+    pub fn new() -> Self {
+        Self {
+            accumulator: 0.,
+            steps: 0,
+            distance: 0.,
+        }
+    }
+
+    /// Reset the clock for a new run
+    pub fn reset(&mut self) {
+        self.accumulator = 0.;
+        self.steps = 0;
+        self.distance = 0.;
+    }
+
+    /// How far between the last two fixed steps the current frame is, from 0 to 1. Used to interpolate what is drawn
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / FIXED_TIMESTEP).clamp(0., 1.)
+    }
+}
+
+/// Move a position through the level's field for one fixed step
+pub fn advance(level: &Level, gradient: &Gradient, position: (f32, f32)) -> (f32, f32) {
+    let dt = level.tick_time * MOVEMENT_SCALE_PER_SECOND * FIXED_TIMESTEP; // time to move through the field each step
+
+    level.integrator.step(|x, y| (gradient.x(x, y), gradient.y(x, y)), position, dt)
+}
+
+/// Mark every gas can within reach of the position as collected
+pub fn collect_gas(level: &Level, position: (f32, f32), gas_collected: &mut [u32]) {
+    for (index, gas_location) in level.gas_locations.iter().enumerate() {
+        if distance(*gas_location, position) < ENDING_LOCATION_ERROR {
+            gas_collected[index] = 1;
+        }
+    }
+}
+
+/// Whether every gas can in the level has been collected
+pub fn all_gas_collected(level: &Level, gas_collected: &[u32]) -> bool {
+    gas_collected.iter().sum::<u32>() == level.gas_locations.len() as u32
+}
+
+/// Whether the position is at the end of the level with all gas collected
+pub fn reached_end(level: &Level, position: (f32, f32), gas_collected: &[u32]) -> bool {
+    all_gas_collected(level, gas_collected) && distance(level.end_location, position) < ENDING_LOCATION_ERROR
+}
+
+/// Whether a level can be finished with a field. Levels that require a conservative field need one, judged by the
+/// same analysis the game shows
+pub fn field_allowed(level: &Level, gradient: &Gradient) -> bool {
+    !level.require_conservative || analyze_field(gradient, level).conservative
+}
+
+/// Simulate one fixed step: move, then collect gas at the new position. Returns whether the end was reached
+pub fn step(level: &Level, gradient: &Gradient, position: &mut (f32, f32), gas_collected: &mut [u32]) -> bool {
+    *position = advance(level, gradient, *position);
+    collect_gas(level, *position, gas_collected);
+    reached_end(level, *position, gas_collected)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Result of simulating a field from the start of a level, without drawing anything
+pub struct Run {
+    pub finished: bool, // whether the end was reached with every gas can collected, using a field the level allows
+    pub field_allowed: bool, // whether the level allows the field (false if it needs a conservative field and this one isn't)
+    pub steps: u64, // number of fixed steps simulated, up to the step the end was reached
+    pub distance: f32, // distance in world units moved
+    pub gas_collected: Vec<u32>, // gas cans collected as a one-hot encoded vector
+    pub position: (f32, f32), // position after the last step
+    pub closest_to_end: f32, // closest the run came to the end
+}
+
+impl Run {
+    /// Seconds of simulation the run took
+    pub fn time(&self) -> f32 {
+        self.steps as f32 * FIXED_TIMESTEP
+    }
+}
+
+/// Simulate a field from the start of a level with the same fixed steps the player moves by, stopping once the
+/// end is reached or after max_steps. As in game, reaching the end with a field the level doesn't allow doesn't
+/// finish it
+pub fn run(level: &Level, gradient: &Gradient, max_steps: u64) -> Run {
+    let field_allowed = field_allowed(level, gradient);
+    let mut position = level.start_location;
+    let mut gas_collected = vec![0; level.gas_locations.len()];
+    let mut distance_moved = 0.;
+    let mut closest_to_end = distance(position, level.end_location);
+
+    for steps in 1..=max_steps {
+        let previous = position;
+        let reached_end = step(level, gradient, &mut position, &mut gas_collected);
+        distance_moved += distance(previous, position);
+        closest_to_end = closest_to_end.min(distance(position, level.end_location));
+
+        if reached_end && field_allowed {
+            return Run { finished: true, field_allowed, steps, distance: distance_moved, gas_collected, position, closest_to_end }
+        }
+    }
+
+    Run { finished: false, field_allowed, steps: max_steps, distance: distance_moved, gas_collected, position, closest_to_end }
+}
+
+/// Distance between two points
+pub fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
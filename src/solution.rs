@@ -0,0 +1,150 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{Gradient, Level};
+use crate::expression::Expression;
+use crate::gradient_field::{GradientOperation, FieldNode};
+use crate::ui::{FieldEntryMode, ExpressionInput, InputTarget};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// Node in a field built from a level's function buttons. Stores button ids rather than functions, so it can be
+/// written to a file and rebuilt from the level's palette
+pub enum SolutionNode {
+    Function(u32), // id of the button the function is on
+    Operation(GradientOperation, Box<SolutionNode>, Box<SolutionNode>), // operation combining the two sides
+    Group(Box<SolutionNode>), // functions grouped together, as if in parentheses
+}
+
+impl SolutionNode {
+    /// Rebuild the node with the functions on a level's buttons for one dimension
+    pub fn to_field_node(&self, functions: &[(String, Expression)]) -> Result<FieldNode, String> {
+        match self {
+            SolutionNode::Function(id) => match functions.get(*id as usize) {
+                Some((_, function)) => Ok(FieldNode::Function { id: *id, function: function.clone() }),
+                None => Err(format!("there is no button {}", id)),
+            },
+            SolutionNode::Operation(operation, lhs, rhs) => Ok(FieldNode::Operation {
+                operation: *operation,
+                lhs: Box::new(lhs.to_field_node(functions)?),
+                rhs: Box::new(rhs.to_field_node(functions)?),
+            }),
+            SolutionNode::Group(inner) => Ok(FieldNode::Group(Box::new(inner.to_field_node(functions)?))),
+        }
+    }
+
+    /// Ids of the buttons used, in order
+    pub fn function_ids(&self) -> Vec<u32> {
+        match self {
+            SolutionNode::Function(id) => vec![*id],
+            SolutionNode::Operation(_, lhs, rhs) => {
+                let mut ids = lhs.function_ids();
+                ids.extend(rhs.function_ids());
+                ids
+            },
+            SolutionNode::Group(inner) => inner.function_ids(),
+        }
+    }
+
+    /// Operations used, in the order the buttons are pressed
+    pub fn operations(&self) -> Vec<GradientOperation> {
+        match self {
+            SolutionNode::Function(_) => Vec::new(),
+            SolutionNode::Operation(operation, lhs, rhs) => {
+                let mut operations = lhs.operations();
+                operations.push(*operation);
+                operations.extend(rhs.operations());
+                operations
+            },
+            SolutionNode::Group(inner) => inner.operations(),
+        }
+    }
+}
+
+impl From<&FieldNode> for SolutionNode {
+    fn from(node: &FieldNode) -> Self {
+        match node {
+            FieldNode::Function { id, .. } => SolutionNode::Function(*id),
+            FieldNode::Operation { operation, lhs, rhs } => SolutionNode::Operation(*operation, Box::new(lhs.as_ref().into()), Box::new(rhs.as_ref().into())),
+            FieldNode::Group(inner) | FieldNode::OpenGroup(inner) => SolutionNode::Group(Box::new(inner.as_ref().into())), // open groups end with the field
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// Field the player built, in a form that can be written to a file
+pub enum FieldSolution {
+    Buttons { // built from the function buttons
+        x: Option<SolutionNode>, // x component, None if no x functions are used
+        y: Option<SolutionNode>, // y component, None if no y functions are used
+    },
+    Typed { // typed into the sandbox
+        x: String, // text of the x component
+        y: String, // text of the y component
+    },
+    Potential(String), // text of the potential the field is the gradient of
+}
+
+impl FieldSolution {
+    /// Field currently built by the player, in the entry mode they are using
+    pub fn from_field<'a>(
+        entry_mode: &FieldEntryMode,
+        gradient: &Gradient,
+        expression_inputs: impl IntoIterator<Item = &'a ExpressionInput>,
+    ) -> Self {
+        let mut texts = (String::new(), String::new(), String::new()); // (x, y, potential) typed in
+        for expression_input in expression_inputs {
+            match expression_input.target {
+                InputTarget::X => texts.0 = expression_input.text.clone(),
+                InputTarget::Y => texts.1 = expression_input.text.clone(),
+                InputTarget::Potential => texts.2 = expression_input.text.clone(),
+            }
+        }
+
+        match entry_mode {
+            FieldEntryMode::Palette => FieldSolution::Buttons {
+                x: gradient.x_component.as_ref().map(SolutionNode::from),
+                y: gradient.y_component.as_ref().map(SolutionNode::from),
+            },
+            FieldEntryMode::FreeForm => FieldSolution::Typed { x: texts.0, y: texts.1 },
+            FieldEntryMode::Potential => FieldSolution::Potential(texts.2),
+        }
+    }
+
+    /// Build the field on a level, as the player would. Fails if it uses a button the level doesn't have, uses a
+    /// button twice in one component, or has text that can't be parsed
+    pub fn to_gradient(&self, level: &Level) -> Result<Gradient, String> {
+        let mut gradient = Gradient::new();
+
+        match self {
+            FieldSolution::Buttons { x, y } => {
+                for (component, dimension) in [(x, "x"), (y, "y")] {
+                    let mut ids = component.as_ref().map(SolutionNode::function_ids).unwrap_or_default();
+                    let used = ids.len();
+                    ids.sort_unstable();
+                    ids.dedup();
+
+                    if ids.len() != used { // each button can only be pressed once
+                        return Err(format!("a button is used more than once in the {} component", dimension))
+                    }
+                }
+
+                gradient.x_component = x.as_ref().map(|x| x.to_field_node(&level.x_functions)).transpose().map_err(|error| format!("x: {}", error))?;
+                gradient.y_component = y.as_ref().map(|y| y.to_field_node(&level.y_functions)).transpose().map_err(|error| format!("y: {}", error))?;
+            },
+            FieldSolution::Typed { x, y } => {
+                if !x.trim().is_empty() {
+                    gradient.set_x_function(Expression::parse(x).map_err(|error| format!("x: {}", error))?);
+                }
+                if !y.trim().is_empty() {
+                    gradient.set_y_function(Expression::parse(y).map_err(|error| format!("y: {}", error))?);
+                }
+            },
+            FieldSolution::Potential(potential) => {
+                if !potential.trim().is_empty() {
+                    gradient.set_potential(Expression::parse(potential).map_err(|error| format!("potential: {}", error))?);
+                }
+            },
+        }
+
+        Ok(gradient)
+    }
+}
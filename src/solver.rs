@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+
+use crate::{Gradient, Level};
+use crate::expression::Expression;
+use crate::gradient_field::{GradientOperation, FieldNode, add_function, group};
+use crate::simulation;
+use crate::solution::{FieldSolution, SolutionNode};
+use crate::score::Score;
+
+use crate::constants::{
+    BUTTONS_PER_DIMENSION,
+    VERIFY_MAX_STEPS,
+    SOLVER_SAMPLE_POINTS,
+};
+
+#[derive(Clone, Debug)]
+/// What the solver searches through
+pub struct SolverOptions {
+    pub operations: Vec<GradientOperation>, // operations that can join one function to the next
+    pub max_functions: u32, // most functions used in each component
+    pub max_steps: u64, // most fixed steps each field is simulated for
+    pub bounds_margin: Option<f32>, // world units a run can go past the level's start, end and gas cans before it's given up on, or None to never give up for leaving the level
+}
+
+impl Default for SolverOptions {
+    fn default() -> Self {
+        Self {
+            operations: vec![
+                GradientOperation::Add,
+                GradientOperation::Subtract,
+                GradientOperation::Multiply,
+                GradientOperation::Divide,
+            ],
+            max_functions: BUTTONS_PER_DIMENSION,
+            max_steps: VERIFY_MAX_STEPS,
+            bounds_margin: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Field found by the solver that finishes a level
+pub struct WinningField {
+    pub field: FieldSolution, // buttons pressed, in the level file's solution format
+    pub x_text: String, // x component as shown in game
+    pub y_text: String, // y component as shown in game
+    pub score: Score, // how well the field finishes the level
+}
+
+/// Operations from a comma separated list of their names, like "add,multiply,compose-x"
+pub fn parse_operations(text: &str) -> Result<Vec<GradientOperation>, String> {
+    text.split(',').map(|name| match name.trim() {
+        "add" => Ok(GradientOperation::Add),
+        "subtract" => Ok(GradientOperation::Subtract),
+        "multiply" => Ok(GradientOperation::Multiply),
+        "divide" => Ok(GradientOperation::Divide),
+        "power" => Ok(GradientOperation::Power),
+        "compose-x" => Ok(GradientOperation::ComposeX),
+        "compose-y" => Ok(GradientOperation::ComposeY),
+        other => Err(format!("unknown operation \"{}\"", other)),
+    }).collect()
+}
+
+/// Every component that can be built by pressing some of a dimension's buttons in order, with an operation before
+/// each button after the first and optionally grouping everything so far between buttons. None is the empty component. Components that give the same values at
+/// SOLVER_SAMPLE_POINTS are only included once, keeping the one with the fewest functions, so changes that do
+/// nothing (like multiplying by 1) aren't tried twice
+pub fn components(functions: &[(String, Expression)], options: &SolverOptions) -> Vec<Option<FieldNode>> {
+    let mut components = vec![None];
+    add_components(None, &mut Vec::new(), functions, options, &mut components);
+    components.sort_by_key(|component| component.as_ref().map_or(0, FieldNode::function_count));
+
+    let mut seen = HashSet::new();
+    components.retain(|component| seen.insert(fingerprint(component)));
+
+    components
+}
+
+/// Add every component built by pressing more buttons after a component, recursively
+fn add_components(
+    component: Option<FieldNode>,
+    used: &mut Vec<u32>,
+    functions: &[(String, Expression)],
+    options: &SolverOptions,
+    components: &mut Vec<Option<FieldNode>>,
+) {
+    if used.len() as u32 >= options.max_functions {
+        return
+    }
+
+    // the first function has no operation before it
+    let operations = if component.is_none() { &[GradientOperation::Add][..] } else { &options.operations[..] };
+
+    for (id, (_, function)) in functions.iter().enumerate() {
+        let id = id as u32;
+        if used.contains(&id) { // each button can only be pressed once
+            continue;
+        }
+
+        for operation in operations {
+            let next = add_function(component.clone(), id, *operation, function.clone());
+            components.push(Some(next.clone()));
+
+            used.push(id);
+            // grouping only changes the field once more functions are added, like (x + y) * -1
+            if next.function_count() > 1 && (used.len() as u32) < options.max_functions {
+                add_components(Some(group(next.clone())), used, functions, options, components);
+            }
+            add_components(Some(next), used, functions, options, components);
+            used.pop();
+        }
+    }
+}
+
+/// Values of a component at SOLVER_SAMPLE_POINTS, rounded so that components giving the same field match.
+/// Values that aren't finite count as 0, as they do when the player moves
+fn fingerprint(component: &Option<FieldNode>) -> Vec<i64> {
+    SOLVER_SAMPLE_POINTS.iter().map(|(x, y)| {
+        let value = component.as_ref().map_or(0., |component| component.evaluate(*x, *y));
+        let value = if value.is_finite() { value } else { 0. };
+        (value * 1000.).round() as i64
+    }).collect()
+}
+
+/// Whether a field finishes a level, including being conservative on levels that need it. Gives up early once the player stops moving, since the run can't finish after
+/// that. With a bounds margin it also gives up once the player goes that far past every point in the level, which
+/// is faster but misses fields that loop out past the margin and come back to finish
+fn finishes(level: &Level, gradient: &Gradient, options: &SolverOptions) -> bool {
+    // without a margin the bounds are unlimited
+    let (mut min_x, mut max_x, mut min_y, mut max_y) = (f32::NEG_INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::INFINITY);
+    if let Some(margin) = options.bounds_margin {
+        let points = level.gas_locations.iter().chain([&level.start_location, &level.end_location]);
+        min_x = points.clone().map(|(x, _)| x - margin).fold(f32::INFINITY, f32::min);
+        max_x = points.clone().map(|(x, _)| x + margin).fold(f32::NEG_INFINITY, f32::max);
+        min_y = points.clone().map(|(_, y)| y - margin).fold(f32::INFINITY, f32::min);
+        max_y = points.map(|(_, y)| y + margin).fold(f32::NEG_INFINITY, f32::max);
+    }
+
+    let mut position = level.start_location;
+    let mut gas_collected = vec![0; level.gas_locations.len()];
+
+    for _ in 0..options.max_steps {
+        let previous = position;
+
+        if simulation::step(level, gradient, &mut position, &mut gas_collected) {
+            return simulation::field_allowed(level, gradient) // only checked once the end is reached, since it's slower
+        }
+
+        let inside = min_x <= position.0 && position.0 <= max_x && min_y <= position.1 && position.1 <= max_y;
+        if !inside || position == previous {
+            return false
+        }
+    }
+
+    false
+}
+
+/// Every field built from a level's buttons, within the options, that finishes the level. Fastest first
+pub fn solve(level: &Level, options: &SolverOptions) -> Vec<WinningField> {
+    let x_components = components(&level.x_functions, options);
+    let y_components = components(&level.y_functions, options);
+
+    let mut winning_fields = Vec::new();
+
+    for x_component in x_components.iter() {
+        for y_component in y_components.iter() {
+            let gradient = Gradient {
+                x_component: x_component.clone(),
+                y_component: y_component.clone(),
+                potential: None,
+            };
+
+            if !finishes(level, &gradient, options) {
+                continue;
+            }
+
+            let run = simulation::run(level, &gradient, options.max_steps);
+            winning_fields.push(WinningField {
+                field: FieldSolution::Buttons {
+                    x: x_component.as_ref().map(SolutionNode::from),
+                    y: y_component.as_ref().map(SolutionNode::from),
+                },
+                x_text: gradient.x_text(),
+                y_text: gradient.y_text(),
+                score: Score::new(&gradient, run.time(), run.distance, 0),
+            });
+        }
+    }
+
+    winning_fields.sort_by(|a, b| a.score.time.total_cmp(&b.score.time));
+    winning_fields
+}
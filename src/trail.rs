@@ -0,0 +1,164 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{Player, GameState, Simulating};
+
+use crate::constants::{
+    TRAIL_RUNS,
+    TRAIL_MAX_POINTS,
+    TRAIL_SAMPLE_DISTANCE,
+    TRAIL_WIDTH,
+    TRAIL_COLORS,
+    TRAIL_MIN_ALPHA,
+};
+
+#[derive(Resource)]
+/// Paths the player actually took in the last few runs of the current level
+pub struct Trails {
+    pub runs: VecDeque<Vec<(f32, f32)>>, // sampled positions of each run, most recent run last
+    pub recording: bool, // whether the most recent run is still being simulated
+    pub level: u32, // level the trails are from
+}
+
+impl Trails {
+    /// New method. Starts with no trails
+    pub fn new() -> Self {
+        Self {
+            runs: VecDeque::new(),
+            recording: false,
+            level: 0,
+        }
+    }
+}
+
+#[derive(Component)]
+/// Struct to label one segment of a trail
+pub struct TrailSegment {
+    pub run: u32, // how many runs ago the segment is from (0 is the most recent run)
+    pub index: u32, // which segment along the run this is
+}
+
+/// Spawn the segments for every trail. They start invisible
+fn trail_setup(mut commands: Commands) {
+    for run in 0..TRAIL_RUNS {
+        for index in 0..TRAIL_MAX_POINTS - 1 {
+            commands
+                .spawn(SpriteBundle {
+                    sprite: Sprite {
+                        color: TRAIL_COLORS[run as usize],
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0., 0., 0.4), // above arrows, below path preview and player
+                    visibility: Visibility { is_visible: false },
+                    ..default()
+                })
+                .insert(TrailSegment { run, index });
+        }
+    }
+}
+
+/// Record where the player goes while simulating. Each time simulating starts, a new run begins
+fn trail_record_system(
+    player: Query<&Player>,
+    game_state: Query<&GameState>,
+    simulating_state: Res<State<Simulating>>,
+    mut trails: ResMut<Trails>,
+) {
+    let player = player.single();
+    let game_state = game_state.single();
+
+    if trails.level != game_state.current_level { // trails from another level would only be confusing
+        trails.runs.clear();
+        trails.recording = false;
+        trails.level = game_state.current_level;
+    }
+
+    match simulating_state.current() {
+        Simulating::Simulating => {
+            if !trails.recording { // start a new run, forgetting the oldest
+                trails.recording = true;
+                trails.runs.push_back(Vec::new());
+                if trails.runs.len() > TRAIL_RUNS as usize {
+                    trails.runs.pop_front();
+                }
+            }
+
+            let position = (player.x, player.y);
+            let needs_sample = match trails.runs.back().and_then(|run| run.last()) {
+                Some(last) => ((last.0 - position.0).powi(2) + (last.1 - position.1).powi(2)).sqrt() >= TRAIL_SAMPLE_DISTANCE,
+                None => true,
+            };
+
+            if needs_sample {
+                let run = trails.runs.back_mut().unwrap(); // run was started above
+                run.push(position);
+                if run.len() > TRAIL_MAX_POINTS as usize { // keep the newest part of long runs
+                    run.remove(0);
+                }
+            }
+        },
+        Simulating::NotSimulating => {
+            if trails.recording { // last run stays visible
+                trails.recording = false;
+            }
+        },
+    }
+}
+
+/// Place trail segments between the recorded positions. The running trail fades out behind the player
+fn trail_display_system(
+    trails: Res<Trails>,
+    mut segments: Query<(&TrailSegment, &mut Sprite, &mut Transform, &mut Visibility)>,
+) {
+    if !trails.is_changed() {
+        return
+    }
+
+    for (segment, mut sprite, mut transform, mut visibility) in segments.iter_mut() {
+        let run = match (trails.runs.len() as u32).checked_sub(segment.run + 1) {
+            Some(run_index) => &trails.runs[run_index as usize],
+            None => { // not that many runs yet
+                visibility.is_visible = false;
+                continue;
+            },
+        };
+
+        let index = segment.index as usize;
+        let (start, end) = match (run.get(index), run.get(index + 1)) {
+            (Some(start), Some(end)) => (*start, *end),
+            _ => {
+                visibility.is_visible = false;
+                continue;
+            },
+        };
+
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+
+        sprite.custom_size = Some(Vec2::new((dx.powi(2) + dy.powi(2)).sqrt(), TRAIL_WIDTH));
+
+        let age = 1. - (index + 1) as f32 / (run.len() - 1) as f32; // 0 for the newest segment, close to 1 for the oldest
+        let alpha = 1. - (1. - TRAIL_MIN_ALPHA) * age;
+        let mut color = TRAIL_COLORS[segment.run as usize];
+        color.set_a(alpha);
+        sprite.color = color;
+
+        transform.translation.x = (start.0 + end.0) / 2.;
+        transform.translation.y = (start.1 + end.1) / 2.;
+        transform.rotation = Quat::from_rotation_z(dy.atan2(dx));
+
+        visibility.is_visible = true;
+    }
+}
+
+/// Plugin for recording and drawing the paths of the last few runs
+pub struct TrailPlugin;
+
+impl Plugin for TrailPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Trails::new());
+        app.add_startup_system(trail_setup);
+        app.add_system(trail_record_system);
+        app.add_system(trail_display_system.after(trail_record_system));
+    }
+}
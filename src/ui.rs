@@ -3,7 +3,12 @@ use bevy::{
     asset::AssetServer,
 };
 
-use crate::{Simulating, Gradient, GradientOperation, GameState, GradientOperationState};
+use crate::{Simulating, Gradient, GradientOperation, GameState, GradientOperationState, LoadingState};
+use crate::expression::{Expression, ParseError};
+use crate::field_render::FieldRenderMode;
+use crate::level::{LoadLevel, load_level_system};
+use crate::level_select::MenuState;
+use crate::history::{self, FieldHistory, HistoryAction};
 
 use crate::constants::{
     NORMAL_BUTTON_COLOR, 
@@ -21,15 +26,59 @@ use crate::constants::{
     NORMAL_BUTTON_TEXT_COLOR,
     PRESSED_BUTTON_TEXT_COLOR,
     NEW_LEVEL_TEXT_FADE_IN_SPEED,
-    LEVEL_COMPLETE_TEXT_COLOR
+    LEVEL_COMPLETE_TEXT_COLOR,
+    INPUT_BOX_COLOR,
+    FOCUSED_INPUT_BOX_COLOR,
+    INPUT_BOX_WIDTH,
+    PARSE_ERROR_TEXT_COLOR,
+    GROUP_BUTTON_SIZE,
+    OPERATION_BUTTON_WIDTH,
+    OPERATION_BUTTON_HEIGHT,
+    OPERATION_BUTTON_SPACING,
 };
 
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+/// enum to store how the player is building the gradient field
+pub enum FieldEntryMode {
+    Palette, // toggling the function buttons for the current level
+    FreeForm, // typing the x and y components in directly
+    Potential, // typing a potential f, whose gradient is the field
+}
+
+impl FieldEntryMode {
+    /// Mode the entry mode button switches to next 
+    pub fn next(&self) -> Self {
+        match self {
+            FieldEntryMode::Palette => FieldEntryMode::FreeForm,
+            FieldEntryMode::FreeForm => FieldEntryMode::Potential,
+            FieldEntryMode::Potential => FieldEntryMode::Palette,
+        }
+    }
+
+    /// Text of the entry mode button, naming the mode it switches to 
+    pub fn button_text(&self) -> &'static str {
+        match self.next() {
+            FieldEntryMode::Palette => "Buttons",
+            FieldEntryMode::FreeForm => "Sandbox",
+            FieldEntryMode::Potential => "Potential",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 /// whether or not a button is for x or y
 pub enum ButtonXY {
     X,
     Y,
 }
 
+/// what an expression input box is typed into 
+pub enum InputTarget {
+    X, // x component of the field
+    Y, // y component of the field
+    Potential, // potential f, the field is its gradient
+}
+
 #[derive(Component)]
 /// information for each button allowing user to select a function
 pub struct GradComponentButton {
@@ -91,8 +140,86 @@ impl NewLevelText {
 pub struct GasCollectedText;
 
 #[derive(Component)]
+/// Struct for a button in the operation selector 
 pub struct OperationButton {
-    pub operation: GradientOperation,
+    pub operation: GradientOperation, // operation selected by this button
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// what a group button does to its component
+pub enum GroupKind {
+    All, // group everything added so far
+    Open, // open a group at the last function added
+    Close, // close the innermost open group
+}
+
+impl GroupKind {
+    /// Text on the button
+    pub fn label(&self) -> &'static str {
+        match self {
+            GroupKind::All => "( )",
+            GroupKind::Open => "(",
+            GroupKind::Close => ")",
+        }
+    }
+}
+
+#[derive(Component)]
+/// Struct to indicate button that groups functions in one component
+pub struct GroupButton {
+    pub xy: ButtonXY, // whether the button is for the x or y component
+    pub kind: GroupKind, // how the button groups functions
+}
+
+#[derive(Component)]
+/// Struct to indicate button that toggles between entering the field with buttons and typing it in
+pub struct EntryModeButton;
+
+#[derive(Component)]
+/// Struct to indicate button that switches how the field is drawn
+pub struct RenderModeButton;
+
+#[derive(Component)]
+/// Struct to indicate button that opens the level select
+pub struct LevelsButton;
+
+#[derive(Component)]
+/// Struct to indicate button that reveals the next hint for the level
+pub struct HintButton;
+
+#[derive(Component)]
+/// Struct to indicate button that undoes or redoes a change to the field
+pub struct HistoryButton {
+    pub action: HistoryAction, // whether the button undoes or redoes
+}
+
+#[derive(Component)]
+/// Struct to label the node holding the function buttons
+pub struct PaletteButtons;
+
+#[derive(Component)]
+/// Struct to label the node holding the expression input boxes
+pub struct ExpressionInputs;
+
+#[derive(Component)]
+/// Text box for typing in one component of the gradient field
+pub struct ExpressionInput {
+    pub target: InputTarget, // what the input is typed into
+    pub text: String, // text typed so far
+    pub focused: bool, // whether typing goes to this input
+    pub error: Option<ParseError>, // error from parsing the text, if it couldn't be parsed
+}
+
+impl ExpressionInput {
+    /// New method. Starts empty and not focused
+    pub fn new(target: InputTarget) -> Self {
+        Self {
+            target,
+            text: String::new(),
+            focused: false,
+            error: None,
+        }
+    }
 }
 
 fn ui_setup(
@@ -125,8 +252,8 @@ fn ui_setup(
                     parent 
                         .spawn(NodeBundle {
                             style: Style {
-                                size: Size::new(Val::Px(BUTTON_WIDTH + 2.*BUTTON_SPACING), Val::Px(BUTTON_HEIGHT + 2.*BUTTON_SPACING)),
-                                justify_content: JustifyContent::Center,
+                                size: Size::new(Val::Px(5.*BUTTON_WIDTH + 6.*BUTTON_SPACING), Val::Px(BUTTON_HEIGHT + 2.*BUTTON_SPACING)),
+                                justify_content: JustifyContent::SpaceEvenly,
                                 align_items: AlignItems::Center,
                                 ..default()
                             },
@@ -157,6 +284,110 @@ fn ui_setup(
                                     ));
                                 })
                                 .insert(SimulatingButton::new()); // add button
+
+                            // place button to toggle between function buttons and typing the field in
+                            parent 
+                                .spawn( 
+                                    ButtonBundle {
+                                        style: Style {
+                                            size: Size::new(Val::Px(BUTTON_WIDTH), Val::Px(BUTTON_HEIGHT)),
+                                            justify_content: JustifyContent::Center,
+                                            align_items: AlignItems::Center,
+                                            ..default()
+                                        },
+                                        background_color: NORMAL_BUTTON_COLOR.into(),
+                                        ..default()
+                                    }
+                                )
+                                .with_children(|parent| {
+                                    parent.spawn(TextBundle::from_section(
+                                        "Sandbox",
+                                        TextStyle {
+                                            font: asset_server.load("fonts/tahoma.ttf"),
+                                            font_size: 20.0, 
+                                            color: Color::rgb(0.9, 0.9, 0.9),
+                                        },
+                                    ));
+                                })
+                                .insert(EntryModeButton);
+
+                            // place button to switch how the field is drawn
+                            parent 
+                                .spawn( 
+                                    ButtonBundle {
+                                        style: Style {
+                                            size: Size::new(Val::Px(BUTTON_WIDTH), Val::Px(BUTTON_HEIGHT)),
+                                            justify_content: JustifyContent::Center,
+                                            align_items: AlignItems::Center,
+                                            ..default()
+                                        },
+                                        background_color: NORMAL_BUTTON_COLOR.into(),
+                                        ..default()
+                                    }
+                                )
+                                .with_children(|parent| {
+                                    parent.spawn(TextBundle::from_section(
+                                        FieldRenderMode::Arrows.button_text(),
+                                        TextStyle {
+                                            font: asset_server.load("fonts/tahoma.ttf"),
+                                            font_size: 20.0, 
+                                            color: Color::rgb(0.9, 0.9, 0.9),
+                                        },
+                                    ));
+                                })
+                                .insert(RenderModeButton);
+
+                            // place button to open the level select
+                            parent 
+                                .spawn( 
+                                    ButtonBundle {
+                                        style: Style {
+                                            size: Size::new(Val::Px(BUTTON_WIDTH), Val::Px(BUTTON_HEIGHT)),
+                                            justify_content: JustifyContent::Center,
+                                            align_items: AlignItems::Center,
+                                            ..default()
+                                        },
+                                        background_color: NORMAL_BUTTON_COLOR.into(),
+                                        ..default()
+                                    }
+                                )
+                                .with_children(|parent| {
+                                    parent.spawn(TextBundle::from_section(
+                                        "Levels",
+                                        TextStyle {
+                                            font: asset_server.load("fonts/tahoma.ttf"),
+                                            font_size: 20.0, 
+                                            color: Color::rgb(0.9, 0.9, 0.9),
+                                        },
+                                    ));
+                                })
+                                .insert(LevelsButton);
+
+                            // place button to reveal a hint
+                            parent 
+                                .spawn( 
+                                    ButtonBundle {
+                                        style: Style {
+                                            size: Size::new(Val::Px(BUTTON_WIDTH), Val::Px(BUTTON_HEIGHT)),
+                                            justify_content: JustifyContent::Center,
+                                            align_items: AlignItems::Center,
+                                            ..default()
+                                        },
+                                        background_color: NORMAL_BUTTON_COLOR.into(),
+                                        ..default()
+                                    }
+                                )
+                                .with_children(|parent| {
+                                    parent.spawn(TextBundle::from_section(
+                                        "Hint",
+                                        TextStyle {
+                                            font: asset_server.load("fonts/tahoma.ttf"),
+                                            font_size: 20.0, 
+                                            color: Color::rgb(0.9, 0.9, 0.9),
+                                        },
+                                    ));
+                                })
+                                .insert(HintButton);
                         });
 
                     parent 
@@ -310,6 +541,32 @@ fn ui_setup(
                                             ..default()
                                         })
                                         .with_children(|parent| {
+                                            for kind in [GroupKind::All, GroupKind::Open, GroupKind::Close] {
+                                                parent // buttons to group the x functions
+                                                    .spawn(ButtonBundle {
+                                                        style: Style {
+                                                            size: Size::new(Val::Px(GROUP_BUTTON_SIZE), Val::Px(GROUP_BUTTON_SIZE)),
+                                                            margin: UiRect::right(Val::Px(BUTTON_SPACING)),
+                                                            justify_content: JustifyContent::Center,
+                                                            align_items: AlignItems::Center,
+                                                            ..default()
+                                                        },
+                                                        background_color: NORMAL_BUTTON_COLOR.into(),
+                                                        ..default()
+                                                    })
+                                                    .with_children(|parent| {
+                                                        parent.spawn(TextBundle::from_section(
+                                                            kind.label(),
+                                                            TextStyle {
+                                                                font: asset_server.load("fonts/tahoma.ttf"),
+                                                                font_size: 16.0,
+                                                                color: Color::rgb(0.9, 0.9, 0.9),
+                                                            },
+                                                        ));
+                                                    })
+                                                    .insert(GroupButton { xy: ButtonXY::X, kind });
+                                            }
+
                                             parent
                                                 .spawn(TextBundle::from_section(
                                                         "x = ",
@@ -334,6 +591,32 @@ fn ui_setup(
                                             ..default()
                                         })
                                         .with_children(|parent| {
+                                            for kind in [GroupKind::All, GroupKind::Open, GroupKind::Close] {
+                                                parent // buttons to group the y functions
+                                                    .spawn(ButtonBundle {
+                                                        style: Style {
+                                                            size: Size::new(Val::Px(GROUP_BUTTON_SIZE), Val::Px(GROUP_BUTTON_SIZE)),
+                                                            margin: UiRect::right(Val::Px(BUTTON_SPACING)),
+                                                            justify_content: JustifyContent::Center,
+                                                            align_items: AlignItems::Center,
+                                                            ..default()
+                                                        },
+                                                        background_color: NORMAL_BUTTON_COLOR.into(),
+                                                        ..default()
+                                                    })
+                                                    .with_children(|parent| {
+                                                        parent.spawn(TextBundle::from_section(
+                                                            kind.label(),
+                                                            TextStyle {
+                                                                font: asset_server.load("fonts/tahoma.ttf"),
+                                                                font_size: 16.0,
+                                                                color: Color::rgb(0.9, 0.9, 0.9),
+                                                            },
+                                                        ));
+                                                    })
+                                                    .insert(GroupButton { xy: ButtonXY::Y, kind });
+                                            }
+
                                             parent 
                                             .spawn(TextBundle::from_section(
                                                     "y = ",
@@ -360,42 +643,72 @@ fn ui_setup(
                                     ..default()
                                 })
                                 .with_children(|parent| {
-                                    // place button to toggle addition/multiplication
+                                    // place selector with a button for each operation
                                     parent
                                     .spawn(NodeBundle {
                                         style: Style {
-                                            size: Size::new(Val::Px(BUTTON_WIDTH + 2.*BUTTON_SPACING), Val::Px(BUTTON_HEIGHT+2.*BUTTON_SPACING)),
+                                            size: Size::new(Val::Px(3.*OPERATION_BUTTON_WIDTH + 6.*OPERATION_BUTTON_SPACING), Val::Px(BUTTON_HEIGHT+2.*BUTTON_SPACING)),
+                                            flex_wrap: FlexWrap::Wrap, // fill rows of buttons
                                             justify_content: JustifyContent::Center,
+                                            align_content: AlignContent::Center,
                                             align_items: AlignItems::Center,
                                             ..default()
                                         },
                                         ..default()
                                     })
                                     .with_children(|parent| {
-                                        parent 
-                                            .spawn(ButtonBundle {
-                                                style: Style {
-                                                    size: Size::new(Val::Px(BUTTON_WIDTH), Val::Px(BUTTON_HEIGHT)),
-                                                    justify_content: JustifyContent::Center,
-                                                    align_items: AlignItems::Center,
+                                        for operation in GradientOperation::ALL {
+                                            parent 
+                                                .spawn(ButtonBundle {
+                                                    style: Style {
+                                                        size: Size::new(Val::Px(OPERATION_BUTTON_WIDTH), Val::Px(OPERATION_BUTTON_HEIGHT)),
+                                                        margin: UiRect::all(Val::Px(OPERATION_BUTTON_SPACING)),
+                                                        justify_content: JustifyContent::Center,
+                                                        align_items: AlignItems::Center,
+                                                        ..default()
+                                                    },
+                                                    background_color: NORMAL_BUTTON_COLOR.into(),
                                                     ..default()
-                                                },
-                                                background_color: NORMAL_BUTTON_COLOR.into(),
-                                                ..default()
-                                            })
-                                            .with_children(|parent| {
-                                                parent.spawn(TextBundle::from_section(
-                                                    "Add",
-                                                    TextStyle {
-                                                        font: asset_server.load("../assets/fonts/tahoma.ttf"),
-                                                        font_size: 20.0,
-                                                        color: Color::rgb(0.9, 0.9, 0.9),
+                                                })
+                                                .with_children(|parent| {
+                                                    parent.spawn(TextBundle::from_section(
+                                                        operation.label(),
+                                                        TextStyle {
+                                                            font: asset_server.load("../assets/fonts/tahoma.ttf"),
+                                                            font_size: 16.0,
+                                                            color: Color::rgb(0.9, 0.9, 0.9),
+                                                        },
+                                                    ));
+                                                })
+                                                .insert(OperationButton { operation });
+                                        }
+
+                                        // undo and redo changes to the field, after the operations
+                                        for (action, label) in [(HistoryAction::Undo, "Undo"), (HistoryAction::Redo, "Redo")] {
+                                            parent 
+                                                .spawn(ButtonBundle {
+                                                    style: Style {
+                                                        size: Size::new(Val::Px(OPERATION_BUTTON_WIDTH), Val::Px(OPERATION_BUTTON_HEIGHT)),
+                                                        margin: UiRect::all(Val::Px(OPERATION_BUTTON_SPACING)),
+                                                        justify_content: JustifyContent::Center,
+                                                        align_items: AlignItems::Center,
+                                                        ..default()
                                                     },
-                                                ));
-                                            })
-                                            .insert(OperationButton {
-                                                operation: GradientOperation::new(), // default starting
-                                            });
+                                                    background_color: NORMAL_BUTTON_COLOR.into(),
+                                                    ..default()
+                                                })
+                                                .with_children(|parent| {
+                                                    parent.spawn(TextBundle::from_section(
+                                                        label,
+                                                        TextStyle {
+                                                            font: asset_server.load("../assets/fonts/tahoma.ttf"),
+                                                            font_size: 16.0,
+                                                            color: Color::rgb(0.9, 0.9, 0.9),
+                                                        },
+                                                    ));
+                                                })
+                                                .insert(HistoryButton { action });
+                                        }
                                     });
                                 });
                         });
@@ -504,7 +817,57 @@ fn ui_setup(
                                             });
                                     }
                                 });
-                        });
+                        })
+                        .insert(PaletteButtons);
+
+                    parent
+                        // expression inputs, hidden until the player switches to typing the field in
+                        .spawn(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(50.), Val::Px(2. * BUTTON_HEIGHT + 3. * BUTTON_SPACING)), // same size as button rows
+                                flex_direction: FlexDirection::Column, // stack inputs in column
+                                justify_content: JustifyContent::Center, 
+                                align_content: AlignContent::Center,
+                                align_items: AlignItems::Center, 
+                                display: Display::None, // start hidden
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for target in [InputTarget::X, InputTarget::Y, InputTarget::Potential] {
+                                parent 
+                                    .spawn(ButtonBundle {
+                                        style: Style {
+                                            size: Size::new(Val::Px(INPUT_BOX_WIDTH), Val::Px(BUTTON_HEIGHT)), // size of input box
+                                            margin: UiRect::all(Val::Px(BUTTON_SPACING / 2.)), // spacing between inputs
+                                            justify_content: JustifyContent::FlexStart, // text starts at the left
+                                            align_items: AlignItems::Center, // center text vertically
+                                            padding: UiRect::all(Val::Px(BUTTON_SPACING)),
+                                            display: match target {
+                                                InputTarget::Potential => Display::None, // only shown when typing a potential
+                                                InputTarget::X | InputTarget::Y => Display::Flex,
+                                            },
+                                            ..default()
+                                        },
+                                        background_color: INPUT_BOX_COLOR.into(),
+                                        ..default()
+                                    })
+                                    .with_children(|parent| {
+                                        parent
+                                            .spawn(TextBundle::from_section(
+                                                "", // filled in by expression_input_text_system
+                                                TextStyle {
+                                                    font: asset_server.load("fonts/tahoma.ttf"),
+                                                    font_size: 20.0,
+                                                    color: Color::rgb(0.9, 0.9, 0.9),
+                                                },
+                                            ));
+                                    })
+                                    .insert(ExpressionInput::new(target));
+                            }
+                        })
+                        .insert(ExpressionInputs);
                 });
         });
 }
@@ -574,38 +937,66 @@ fn current_level_text_update(
     }
 }
 
-/// update system for operation choice button
-fn operation_state_button_handling(
+/// update system for operation selector buttons. The button for the current operation stays pressed
+pub fn operation_state_button_handling(
+    mut buttons: Query<(&Interaction, &mut BackgroundColor, &OperationButton), With<Button>>,
+    mut operation_state: ResMut<State<GradientOperationState>>,
+    mut history: ResMut<FieldHistory>,
+) {
+    let mut selected = operation_state.current().operation(); // operation currently selected
+
+    for (interaction, _, button) in buttons.iter() {
+        if *interaction == Interaction::Clicked && button.operation != selected { // select clicked operation
+            operation_state.overwrite_set(GradientOperationState::from_operation(button.operation)).unwrap(); // update state, replacing any change already queued this frame
+            history.record_operation(selected, button.operation);
+            selected = button.operation;
+        }
+    }
+
+    for (interaction, mut color, button) in buttons.iter_mut() {
+        *color = match (*interaction, button.operation == selected) {
+            (Interaction::Hovered, true) => HOVERED_PRESSED_BUTTON_COLOR.into(), // different color change on hover when selected
+            (Interaction::Hovered, false) => HOVERED_BUTTON_COLOR.into(), // color change to indicate hover
+            (_, true) => PRESSED_BUTTON_COLOR.into(), // selected operation looks pressed 
+            (_, false) => NORMAL_BUTTON_COLOR.into(),
+        };
+    }
+}
+
+/// update system for buttons grouping the functions of a component, all at once or by opening and closing a group
+pub fn group_button_system(
     mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor, &Children, &mut OperationButton),
+        (&Interaction, &mut BackgroundColor, &GroupButton),
         (Changed<Interaction>, With<Button>),
     >,
-    mut text_query: Query<&mut Text>,
-    mut operation_state: ResMut<State<GradientOperationState>>,
+    mut gradient: Query<&mut Gradient>,
+    mut simulating_state: ResMut<State<Simulating>>,
+    mut history: ResMut<FieldHistory>,
 ) {
-    for  (interaction, mut color, children, mut button) in &mut interaction_query { // get button interaction
-        let mut text = text_query.get_mut(children[0]).unwrap(); // get button text
-
+    for (interaction, mut color, button) in &mut interaction_query {
         match *interaction {
             Interaction::Clicked => {
                 *color = PRESSED_BUTTON_COLOR.into(); // change button color 
 
-                match button.operation {
-                    GradientOperation::Add => {
-                        button.operation = GradientOperation::Multiply; // cycle through operations 
-
-                        text.sections[0].value = String::from("Multiply"); // update text
-
-                        operation_state.set(GradientOperationState::Multiply).unwrap(); // update state
-                    },
-                    GradientOperation::Multiply => {
-                        button.operation = GradientOperation::Add; // cycle through operations 
-
-                        text.sections[0].value = String::from("Add"); // update text
+                // changing the field stops simulating 
+                match simulating_state.current() {
+                    Simulating::Simulating => {
+                        simulating_state.set(Simulating::NotSimulating).unwrap(); // only change if not already in state
+                    }
+                    Simulating::NotSimulating => {}
+                }
 
-                        operation_state.set(GradientOperationState::Add).unwrap(); // update state
-                    },
+                let mut gradient = gradient.single_mut(); // get gradient
+                let before = history::component(&gradient, button.xy).clone();
+                match (button.xy, button.kind) {
+                    (ButtonXY::X, GroupKind::All) => gradient.group_x_functions(),
+                    (ButtonXY::Y, GroupKind::All) => gradient.group_y_functions(),
+                    (ButtonXY::X, GroupKind::Open) => gradient.open_x_group(),
+                    (ButtonXY::Y, GroupKind::Open) => gradient.open_y_group(),
+                    (ButtonXY::X, GroupKind::Close) => gradient.close_x_group(),
+                    (ButtonXY::Y, GroupKind::Close) => gradient.close_y_group(),
                 }
+                history.record_component(button.xy, before, history::component(&gradient, button.xy).clone()); // so grouping can be undone
             },
             Interaction::Hovered => {
                 *color = HOVERED_BUTTON_COLOR.into(); // change color on hover 
@@ -723,7 +1114,7 @@ fn simulating_button_check(
     }
 }
 
-fn grad_component_button_system(
+pub fn grad_component_button_system(
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor, &Children, &mut GradComponentButton),
         (Changed<Interaction>, With<Button>),
@@ -733,6 +1124,7 @@ fn grad_component_button_system(
     game_state: Query<&GameState>,
     mut simulating_state: ResMut<State<Simulating>>,
     operation_state: Res<State<GradientOperationState>>,
+    mut history: ResMut<FieldHistory>,
 ) {
     let mut gradient = gradient.single_mut(); // get gradient
     let game_state = game_state.single(); // get game state
@@ -749,6 +1141,8 @@ fn grad_component_button_system(
                     Simulating::NotSimulating => {}
                 }
 
+                let before = history::component(&gradient, button.xy).clone(); // component before this button changes it
+
                 if button.used { // if button is already used 
                     // remove corresponding function from gradient 
                     match button.xy {
@@ -770,9 +1164,8 @@ fn grad_component_button_system(
                             // add function to gradient 
                             gradient.add_x_function(
                                 button.id, // take button id as function id
-                                match operation_state.current() {GradientOperationState::Add => {GradientOperation::Add}, GradientOperationState::Multiply => {GradientOperation::Multiply}}, // operation based on state 
-                                game_state.level_info[game_state.current_level as usize].x_functions[button.id as usize].1, // get function
-                                game_state.level_info[game_state.current_level as usize].x_functions[button.id as usize].0.clone() // get string representing function
+                                operation_state.current().operation(), // operation based on state 
+                                game_state.level_info[game_state.current_level as usize].x_functions[button.id as usize].1.clone(), // get function
                             );
 
                         },
@@ -780,9 +1173,8 @@ fn grad_component_button_system(
                             // add function to gradient 
                             gradient.add_y_function(
                                 button.id, // take button id as function id
-                                match operation_state.current() {GradientOperationState::Add => {GradientOperation::Add}, GradientOperationState::Multiply => {GradientOperation::Multiply}}, // operation based on state
-                                game_state.level_info[game_state.current_level as usize].y_functions[button.id as usize].1, // get function
-                                game_state.level_info[game_state.current_level as usize].y_functions[button.id as usize].0.clone() // get string representing function
+                                operation_state.current().operation(), // operation based on state
+                                game_state.level_info[game_state.current_level as usize].y_functions[button.id as usize].1.clone(), // get function
                             );
                         },
                     }
@@ -791,6 +1183,8 @@ fn grad_component_button_system(
                     text.sections[0].style.color = PRESSED_BUTTON_TEXT_COLOR; // change text color to pressed
                     button.used = true; // set button to used 
                 }
+
+                history.record_component(button.xy, before, history::component(&gradient, button.xy).clone()); // so the change can be undone
             },
             Interaction::Hovered => {
                 if button.used {
@@ -825,44 +1219,391 @@ fn grad_component_button_system(
     }
 }
 
+/// update system for the button switching between function buttons and typing the field in
+fn entry_mode_button_system(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<EntryModeButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+    mut entry_mode: ResMut<State<FieldEntryMode>>,
+    mut simulating_state: ResMut<State<Simulating>>,
+    mut gradient: Query<&mut Gradient>,
+    mut grad_buttons: Query<(&Children, &mut BackgroundColor, &mut GradComponentButton), Without<EntryModeButton>>,
+    mut expression_inputs: Query<&mut ExpressionInput>,
+) {
+    for (interaction, mut color, children) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                *color = PRESSED_BUTTON_COLOR.into(); // change button color 
+
+                // switching modes stops simulating 
+                match simulating_state.current() {
+                    Simulating::Simulating => {
+                        simulating_state.set(Simulating::NotSimulating).unwrap(); // only change if not already in state
+                    }
+                    Simulating::NotSimulating => {}
+                }
+
+                gradient.single_mut().clear_field(); // field from one mode doesn't carry over to the other
+
+                for (button_children, mut background_color, mut grad_component_button) in grad_buttons.iter_mut() { // reset gradient buttons
+                    let mut button_text = text_query.get_mut(button_children[0]).unwrap();
+
+                    *background_color = NORMAL_BUTTON_COLOR.into(); // update button background 
+                    button_text.sections[0].style.color = NORMAL_BUTTON_TEXT_COLOR; // update button text color
+                    grad_component_button.used = false; // update button used
+                }
+
+                for mut expression_input in expression_inputs.iter_mut() { // reset expression inputs
+                    expression_input.text.clear();
+                    expression_input.focused = false;
+                    expression_input.error = None;
+                }
+
+                let next_mode = entry_mode.current().next();
+
+                let mut text = text_query.get_mut(children[0]).unwrap(); // get button text
+                text.sections[0].value = String::from(next_mode.button_text()); // button now names the mode after this one
+
+                entry_mode.set(next_mode).unwrap(); // update state
+            },
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON_COLOR.into(); // change color on hover 
+            },
+            Interaction::None => {
+                *color = NORMAL_BUTTON_COLOR.into(); // change color back on no hover/interaction   
+            },
+        }
+    }
+}
+
+/// switch to the next way of drawing the field. Unlike switching entry mode, this keeps the field and keeps simulating
+fn render_mode_button_system(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<RenderModeButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+    mut render_mode: ResMut<State<FieldRenderMode>>,
+) {
+    for (interaction, mut color, children) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                *color = PRESSED_BUTTON_COLOR.into(); // change button color 
+
+                let next_mode = render_mode.current().next();
+
+                let mut text = text_query.get_mut(children[0]).unwrap(); // get button text
+                text.sections[0].value = String::from(next_mode.button_text()); // button now names the mode after this one
+
+                render_mode.set(next_mode).unwrap(); // update state
+            },
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON_COLOR.into(); // change color on hover 
+            },
+            Interaction::None => {
+                *color = NORMAL_BUTTON_COLOR.into(); // change color back on no hover/interaction   
+            },
+        }
+    }
+}
+
+/// open the level select 
+fn levels_button_system(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<LevelsButton>),
+    >,
+    mut menu_state: ResMut<State<MenuState>>,
+) {
+    for (interaction, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                *color = PRESSED_BUTTON_COLOR.into(); // change button color 
+
+                if *menu_state.current() != MenuState::LevelSelect {
+                    menu_state.overwrite_set(MenuState::LevelSelect).unwrap(); // update state
+                }
+            },
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON_COLOR.into(); // change color on hover 
+            },
+            Interaction::None => {
+                *color = NORMAL_BUTTON_COLOR.into(); // change color back on no hover/interaction   
+            },
+        }
+    }
+}
+
+/// reset the function buttons to the functions of a newly loaded level 
+fn grad_button_reset_system(
+    mut load_level: EventReader<LoadLevel>,
+    game_state: Query<&GameState>,
+    mut text_query: Query<&mut Text>,
+    mut grad_buttons: Query<(&Children, &mut BackgroundColor, &mut GradComponentButton)>,
+) {
+    if load_level.iter().count() == 0 { // no level loaded this frame
+        return
+    }
+
+    let game_state = game_state.single();
+    let level = &game_state.level_info[game_state.current_level as usize];
+
+    for (children, mut background_color, mut grad_component_button) in grad_buttons.iter_mut() {
+        let mut text = text_query.get_mut(children[0]).unwrap();
+        
+        *background_color = NORMAL_BUTTON_COLOR.into(); // update button background 
+        text.sections[0].style.color = NORMAL_BUTTON_TEXT_COLOR; // update button text color
+        grad_component_button.used = false; // update button used
+
+        text.sections[0].value = match grad_component_button.xy {
+            ButtonXY::X => level.x_functions[grad_component_button.id as usize].0.clone(), // update button text
+            ButtonXY::Y => level.y_functions[grad_component_button.id as usize].0.clone(),
+        };
+    }
+}
+
+/// show either the function buttons or the expression inputs for the entry mode 
+fn entry_mode_display_system(
+    entry_mode: Res<State<FieldEntryMode>>,
+    mut palette_buttons: Query<&mut Style, (With<PaletteButtons>, Without<ExpressionInputs>, Without<ExpressionInput>)>,
+    mut expression_inputs: Query<&mut Style, (With<ExpressionInputs>, Without<PaletteButtons>, Without<ExpressionInput>)>,
+    mut inputs: Query<(&mut Style, &ExpressionInput), (Without<PaletteButtons>, Without<ExpressionInputs>)>,
+) {
+    if !entry_mode.is_changed() { // only need to update when mode changes
+        return
+    }
+
+    for mut style in palette_buttons.iter_mut() {
+        style.display = match entry_mode.current() {
+            FieldEntryMode::Palette => Display::Flex,
+            FieldEntryMode::FreeForm | FieldEntryMode::Potential => Display::None,
+        };
+    }
+
+    for mut style in expression_inputs.iter_mut() {
+        style.display = match entry_mode.current() {
+            FieldEntryMode::Palette => Display::None,
+            FieldEntryMode::FreeForm | FieldEntryMode::Potential => Display::Flex,
+        };
+    }
+
+    for (mut style, expression_input) in inputs.iter_mut() { // component inputs for sandbox, potential input for potential
+        style.display = match (entry_mode.current(), &expression_input.target) {
+            (FieldEntryMode::FreeForm, InputTarget::X | InputTarget::Y) => Display::Flex,
+            (FieldEntryMode::Potential, InputTarget::Potential) => Display::Flex,
+            _ => Display::None,
+        };
+    }
+}
+
+/// focus the expression input that was clicked on. Clicking anywhere else unfocuses all inputs
+fn expression_input_focus_system(
+    mut expression_inputs: Query<(&Interaction, &mut ExpressionInput)>,
+    mouse_buttons: Res<Input<MouseButton>>,
+) {
+    if !mouse_buttons.just_pressed(MouseButton::Left) {
+        return
+    }
+
+    for (interaction, mut expression_input) in expression_inputs.iter_mut() {
+        expression_input.focused = *interaction == Interaction::Clicked;
+    }
+}
+
+/// type into the focused expression input and update the gradient with the parsed expression
+pub fn expression_input_typing_system(
+    mut received_characters: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut expression_inputs: Query<&mut ExpressionInput>,
+    mut gradient: Query<&mut Gradient>,
+    mut simulating_state: ResMut<State<Simulating>>,
+) {
+    let typed: Vec<char> = received_characters.iter().map(|event| event.char).filter(|character| !character.is_control()).collect(); // backspace and enter come through as control characters
+    let mut gradient = gradient.single_mut(); // get gradient
+
+    for mut expression_input in expression_inputs.iter_mut() {
+        if !expression_input.focused {
+            continue;
+        }
+
+        let mut changed = !typed.is_empty();
+        expression_input.text.extend(typed.iter());
+
+        if keys.just_pressed(KeyCode::Back) { // delete last character
+            changed |= expression_input.text.pop().is_some();
+        }
+
+        if keys.just_pressed(KeyCode::Return) || keys.just_pressed(KeyCode::Escape) { // finish typing 
+            expression_input.focused = false;
+        }
+
+        if !changed {
+            continue;
+        }
+
+        // editing the field stops simulating 
+        match simulating_state.current() {
+            Simulating::Simulating => {
+                simulating_state.overwrite_set(Simulating::NotSimulating).unwrap(); // only change if not already in state, replacing a change already queued this frame
+            }
+            Simulating::NotSimulating => {}
+        }
+
+        // an empty or invalid expression leaves the component at 0 
+        let parsed = if expression_input.text.trim().is_empty() {
+            None
+        } else {
+            match Expression::parse(&expression_input.text) {
+                Ok(expression) => Some(expression),
+                Err(error) => {
+                    expression_input.error = Some(error);
+                    None
+                },
+            }
+        };
+
+        if parsed.is_some() || expression_input.text.trim().is_empty() {
+            expression_input.error = None; // text is valid again
+        }
+
+        match (&expression_input.target, parsed) {
+            (InputTarget::X, Some(expression)) => gradient.set_x_function(expression),
+            (InputTarget::Y, Some(expression)) => gradient.set_y_function(expression),
+            (InputTarget::Potential, Some(expression)) => gradient.set_potential(expression), // field becomes the gradient of f
+            (InputTarget::X, None) => gradient.x_component = None,
+            (InputTarget::Y, None) => gradient.y_component = None,
+            (InputTarget::Potential, None) => gradient.clear_field(),
+        }
+    }
+}
+
+/// update text and color of expression inputs 
+fn expression_input_text_system(
+    mut expression_inputs: Query<(&ExpressionInput, &Children, &mut BackgroundColor)>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (expression_input, children, mut color) in expression_inputs.iter_mut() {
+        let mut text = text_query.get_mut(children[0]).unwrap(); // get text of input
+
+        let label = match expression_input.target {
+            InputTarget::X => "x",
+            InputTarget::Y => "y",
+            InputTarget::Potential => "f",
+        };
+
+        if expression_input.focused {
+            text.sections[0].value = format!("{} = {}|", label, expression_input.text); // show cursor at end of text
+            *color = FOCUSED_INPUT_BOX_COLOR.into();
+        } else {
+            text.sections[0].value = format!("{} = {}", label, expression_input.text);
+            *color = INPUT_BOX_COLOR.into();
+        }
+    }
+}
+
+/// find the parse error for one component, if the player is typing the field (or its potential) in and the text can't be parsed
+fn component_parse_error(
+    expression_inputs: &Query<&ExpressionInput>,
+    entry_mode: &State<FieldEntryMode>,
+    xy: ButtonXY,
+) -> Option<ParseError> {
+    let input_used = |expression_input: &&ExpressionInput| match (entry_mode.current(), &expression_input.target, &xy) {
+        (FieldEntryMode::FreeForm, InputTarget::X, ButtonXY::X) | (FieldEntryMode::FreeForm, InputTarget::Y, ButtonXY::Y) => true,
+        (FieldEntryMode::Potential, InputTarget::Potential, _) => true, // both components come from the potential
+        _ => false, // nothing typed in for this component
+    };
+
+    expression_inputs
+        .iter()
+        .find(input_used)
+        .and_then(|expression_input| expression_input.error.clone())
+}
+
+/// label for one component of the field. When the field comes from a potential, the components are its partial derivatives
+fn component_label(entry_mode: &State<FieldEntryMode>, xy: ButtonXY) -> &'static str {
+    match (entry_mode.current(), xy) {
+        (FieldEntryMode::Potential, ButtonXY::X) => "∂f/∂x",
+        (FieldEntryMode::Potential, ButtonXY::Y) => "∂f/∂y",
+        (_, ButtonXY::X) => "x",
+        (_, ButtonXY::Y) => "y",
+    }
+}
+
 /// function for updating the x gradient text 
 fn x_gradient_text_system(
     mut text_query: Query<&mut Text, With<XGradientText>>,
     gradient: Query<&Gradient>,
+    expression_inputs: Query<&ExpressionInput>,
+    entry_mode: Res<State<FieldEntryMode>>,
 ) {
     let gradient = gradient.single(); // get gradient
 
     let mut text = text_query.single_mut(); // get text
 
-    // update text 
-    text.sections[0].value = format!("x = {}", gradient.x_text());
+    // update text, showing the parse error instead if there is one
+    match component_parse_error(&expression_inputs, &entry_mode, ButtonXY::X) {
+        Some(error) => {
+            text.sections[0].value = format!("{} = ? {}", component_label(&entry_mode, ButtonXY::X), error);
+            text.sections[0].style.color = PARSE_ERROR_TEXT_COLOR;
+        },
+        None => {
+            text.sections[0].value = format!("{} = {}", component_label(&entry_mode, ButtonXY::X), gradient.x_text());
+            text.sections[0].style.color = Color::rgb(0.9, 0.9, 0.9);
+        },
+    }
 }
 
 /// function for updating the y gradient text
 fn y_gradient_text_system(
     mut text_query: Query<&mut Text, With<YGradientText>>,
     gradient: Query<&Gradient>,
+    expression_inputs: Query<&ExpressionInput>,
+    entry_mode: Res<State<FieldEntryMode>>,
 ) {
     let gradient = gradient.single(); // get gradient
 
     let mut text = text_query.single_mut(); // get text
 
-    // update text 
-    text.sections[0].value = format!("y = {}", gradient.y_text());
+    // update text, showing the parse error instead if there is one
+    match component_parse_error(&expression_inputs, &entry_mode, ButtonXY::Y) {
+        Some(error) => {
+            text.sections[0].value = format!("{} = ? {}", component_label(&entry_mode, ButtonXY::Y), error);
+            text.sections[0].style.color = PARSE_ERROR_TEXT_COLOR;
+        },
+        None => {
+            text.sections[0].value = format!("{} = {}", component_label(&entry_mode, ButtonXY::Y), gradient.y_text());
+            text.sections[0].style.color = Color::rgb(0.9, 0.9, 0.9);
+        },
+    }
 }
 pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(ui_setup);
-        app.add_system(grad_component_button_system);
+        app.add_state(FieldEntryMode::Palette); // start with function buttons
+        app.add_system(entry_mode_button_system);
+        app.add_system(entry_mode_display_system);
+        app.add_system(render_mode_button_system);
+        app.add_system(levels_button_system);
+        app.add_system(expression_input_focus_system);
+        app.add_system(expression_input_typing_system);
+        app.add_system(expression_input_text_system);
         app.add_system(simulating_button_system);
         app.add_system(simulating_button_check);
         app.add_system(x_gradient_text_system);
         app.add_system(y_gradient_text_system);
         app.add_system(operation_state_button_handling);
+        app.add_system(group_button_system);
         app.add_system(current_level_text_update);
         app.add_system(new_level_text_system);
-        app.add_system(update_gas_collected_text);
+        app.add_system_set(
+            SystemSet::on_update(LoadingState::Loaded) // these systems need level info
+                .with_system(grad_component_button_system)
+                .with_system(grad_button_reset_system.after(load_level_system)) // needs the new level
+                .with_system(update_gas_collected_text)
+        );
     }
 }
\ No newline at end of file
@@ -0,0 +1,75 @@
+use std::fs;
+
+use crate::Level;
+use crate::level_loader::parse_level_file;
+use crate::simulation::{self, Run};
+use crate::solution::FieldSolution;
+
+use crate::constants::{VERIFY_MAX_STEPS, ENDING_LOCATION_ERROR, LEVEL_FILE_PATH};
+
+/// Path of the level file the game plays, from the root of the project
+pub fn level_file_path() -> String {
+    format!("assets/{}", LEVEL_FILE_PATH)
+}
+
+/// Read and validate a level file, without starting the game
+pub fn load_levels(path: &str) -> Result<Vec<Level>, String> {
+    let bytes = fs::read(path).map_err(|error| format!("couldn't read {}: {}", path, error))?;
+    parse_level_file(&bytes).map_err(|error| error.to_string())
+}
+
+/// Level with a number, counting from 1, or with an id
+pub fn find_level<'a>(levels: &'a [Level], name: &str) -> Option<&'a Level> {
+    match name.parse::<usize>() {
+        Ok(number) => levels.get(number.checked_sub(1)?),
+        Err(_) => levels.iter().find(|level| level.id == name),
+    }
+}
+
+/// Parse a field written the same way as solutions in the level file, like "Buttons(x: Some(Function(1)), y: None)"
+pub fn parse_field(text: &str) -> Result<FieldSolution, String> {
+    ron::from_str(text).map_err(|error| error.to_string())
+}
+
+/// Write a field the same way as solutions in the level file, so it can be pasted into it
+pub fn field_text(field: &FieldSolution) -> String {
+    ron::to_string(field).unwrap_or_default()
+}
+
+/// Simulate a field on a level from its start, moving the same way the player does, until the end is reached or
+/// VERIFY_MAX_STEPS have passed. Fails if the field can't be built from the level's buttons
+pub fn verify(level: &Level, field: &FieldSolution) -> Result<Run, String> {
+    let gradient = field.to_gradient(level)?;
+    Ok(simulation::run(level, &gradient, VERIFY_MAX_STEPS))
+}
+
+/// Text describing a run on a level: which gas cans were reached, and whether the end was
+pub fn report(level: &Level, run: &Run) -> String {
+    let mut text = format!(
+        "level {} ({}): {}",
+        level.level_number + 1, level.id, if run.finished { "finished" } else { "not finished" },
+    );
+
+    for (index, (gas_location, collected)) in level.gas_locations.iter().zip(run.gas_collected.iter()).enumerate() {
+        text.push_str(&format!(
+            "\n  gas can {} at ({}, {}): {}",
+            index + 1, gas_location.0, gas_location.1, if *collected == 1 { "reached" } else { "not reached" },
+        ));
+    }
+
+    let (end_x, end_y) = level.end_location;
+    text.push_str(&if run.finished {
+        format!("\n  end at ({}, {}): reached after {:.2} s, path length {:.1}", end_x, end_y, run.time(), run.distance)
+    } else if !run.field_allowed {
+        format!("\n  end at ({}, {}): can't be finished, the level needs a conservative field and this one has curl", end_x, end_y)
+    } else if run.closest_to_end < ENDING_LOCATION_ERROR {
+        format!("\n  end at ({}, {}): passed before every gas can was collected", end_x, end_y)
+    } else {
+        format!(
+            "\n  end at ({}, {}): not reached, closest {:.2} away (needs to be within {}), run ended at ({:.2}, {:.2})",
+            end_x, end_y, run.closest_to_end, ENDING_LOCATION_ERROR, run.position.0, run.position.1,
+        )
+    });
+
+    text
+}
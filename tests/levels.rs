@@ -0,0 +1,83 @@
+use the_game_of_gradients::verifier;
+
+#[test]
+fn level_file_is_valid() {
+    let levels = verifier::load_levels(&verifier::level_file_path());
+    assert!(levels.is_ok(), "{}", levels.unwrap_err());
+}
+
+#[test]
+fn every_level_is_finished_by_its_solution() {
+    let levels = verifier::load_levels(&verifier::level_file_path()).unwrap();
+
+    for level in levels.iter() {
+        let solution = level.solution.as_ref().unwrap_or_else(|| panic!("level {} ({}) has no solution", level.level_number + 1, level.id));
+        let run = verifier::verify(level, solution).unwrap();
+
+        assert!(run.finished, "{}", verifier::report(level, &run));
+    }
+}
+
+#[test]
+fn unfinished_field_is_reported() {
+    let levels = verifier::load_levels(&verifier::level_file_path()).unwrap();
+    let level = verifier::find_level(&levels, "linear").unwrap();
+
+    let field = verifier::parse_field("Buttons(x: Some(Function(1)), y: None)").unwrap(); // only moves right
+    let run = verifier::verify(level, &field).unwrap();
+
+    assert!(!run.finished);
+    assert!(verifier::report(level, &run).contains("not reached"));
+}
+
+#[test]
+fn button_used_twice_is_invalid() {
+    let levels = verifier::load_levels(&verifier::level_file_path()).unwrap();
+    let level = verifier::find_level(&levels, "1").unwrap();
+
+    let field = verifier::parse_field("Buttons(x: Some(Operation(Add, Function(1), Function(1))), y: None)").unwrap();
+
+    assert!(verifier::verify(level, &field).is_err());
+}
+
+#[test]
+fn unfinished_solution_only_loses_the_par() {
+    let text = std::fs::read_to_string(verifier::level_file_path()).unwrap().replacen(
+        "solution: Some(Buttons(x: Some(Function(1)), y: Some(Function(1)))),",
+        "solution: Some(Buttons(x: Some(Function(1)), y: None)),", // only moves right, so never reaches the end
+        1,
+    );
+    let path = std::env::temp_dir().join("unfinished_solution.levels.ron");
+    std::fs::write(&path, text).unwrap();
+
+    let levels = verifier::load_levels(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    let levels = levels.unwrap();
+    let level = verifier::find_level(&levels, "linear").unwrap();
+    assert!(level.solution.is_some() && level.par.is_none());
+    assert!(!verifier::verify(level, level.solution.as_ref().unwrap()).unwrap().finished);
+    assert!(verifier::find_level(&levels, "cosine").unwrap().par.is_some());
+}
+
+#[test]
+fn field_with_curl_does_not_finish_a_conservative_level() {
+    let text = std::fs::read_to_string(verifier::level_file_path()).unwrap().replacen(
+        "id: \"circle-gas\",",
+        "id: \"circle-gas\", require_conservative: true,", // its solution circles, with a curl of 2
+        1,
+    );
+    let path = std::env::temp_dir().join("conservative_level.levels.ron");
+    std::fs::write(&path, text).unwrap();
+
+    let levels = verifier::load_levels(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    let levels = levels.unwrap();
+    let level = verifier::find_level(&levels, "circle-gas").unwrap();
+    let run = verifier::verify(level, level.solution.as_ref().unwrap()).unwrap();
+
+    assert!(!run.finished && !run.field_allowed);
+    assert!(verifier::report(level, &run).contains("needs a conservative field"));
+    assert!(level.par.is_none());
+}
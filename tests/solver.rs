@@ -0,0 +1,46 @@
+use the_game_of_gradients::verifier;
+use the_game_of_gradients::solver::{self, SolverOptions};
+
+#[test]
+fn finds_known_solution() {
+    let levels = verifier::load_levels(&verifier::level_file_path()).unwrap();
+    let level = verifier::find_level(&levels, "linear").unwrap();
+
+    let options = SolverOptions { max_functions: 1, ..SolverOptions::default() }; // the solution only needs one function in each component
+    let winning_fields = solver::solve(level, &options);
+
+    assert!(winning_fields.iter().any(|winning_field| Some(&winning_field.field) == level.solution.as_ref()));
+}
+
+#[test]
+fn every_winning_field_finishes() {
+    let levels = verifier::load_levels(&verifier::level_file_path()).unwrap();
+    let level = verifier::find_level(&levels, "one-x").unwrap();
+
+    let options = SolverOptions { max_functions: 2, ..SolverOptions::default() };
+
+    for winning_field in solver::solve(level, &options) {
+        assert!(verifier::verify(level, &winning_field.field).unwrap().finished, "{}", verifier::field_text(&winning_field.field));
+    }
+}
+
+#[test]
+fn parses_operations() {
+    assert_eq!(solver::parse_operations("add, compose-x").unwrap().len(), 2);
+    assert!(solver::parse_operations("add,modulo").is_err());
+}
+
+#[test]
+fn tries_grouping_everything_so_far() {
+    let levels = verifier::load_levels(&verifier::level_file_path()).unwrap();
+    let level = verifier::find_level(&levels, "product").unwrap(); // y buttons are x, y, 1 and -1
+
+    let options = SolverOptions { max_functions: 3, ..SolverOptions::default() };
+    let components = solver::components(&level.y_functions, &options);
+
+    // (x + 1) * y can't be built without grouping, since y can only be pressed once
+    let points = [(0.5, -2.), (3., 1.5), (-4., 2.5)];
+    assert!(components.iter().flatten().any(|component| {
+        points.iter().all(|(x, y)| (component.evaluate(*x, *y) - (x + 1.) * y).abs() < 1e-4)
+    }));
+}